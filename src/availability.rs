@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use serde_json::{self, Value};
+use tiny_keccak::sha3_256;
+use network::Network;
+use network::node::Node;
+use network::prefix::Name;
+use params::{deserialize_loaded, is_json, ParamsError};
+
+/// A single session of a node in an availability trace
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// the id of the node in the trace
+    pub id: String,
+    /// the time the node came online
+    pub join: f64,
+    /// the time the node went offline; `None` if it stayed online until the end of the trace
+    pub leave: Option<f64>,
+}
+
+/// An availability trace, e.g. from a measurement study of a P2P network: the sessions of the
+/// nodes, loaded from a CSV file with `id,join,leave` rows or a JSON array of objects with these
+/// fields. An empty leave time means that the node never left.
+/// Written as the path of the file it was loaded from, and serialised with the sessions, so that
+/// the file isn't read again.
+#[derive(Clone, PartialEq)]
+pub struct Availability {
+    file: String,
+    sessions: Arc<Vec<Session>>,
+}
+
+impl Availability {
+    /// Loads the trace from a file, as JSON if it has a `.json` extension and as CSV otherwise
+    pub fn load(file: &str) -> Result<Availability, ParamsError> {
+        let mut contents = String::new();
+        let _ = File::open(file)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|err| ParamsError::Io(file.to_owned(), err))?;
+        let sessions = if is_json(file) {
+            parse_json(&contents)
+        } else {
+            parse_csv(&contents)
+        }.map_err(|err| ParamsError::Format(file.to_owned(), err))?;
+        Availability::new(file.to_owned(), sessions)
+    }
+
+    /// Checks the sessions loaded from the file
+    fn new(file: String, sessions: Vec<Session>) -> Result<Availability, ParamsError> {
+        if let Some(session) = sessions
+            .iter()
+            .find(|s| !(s.join >= 0.0 && s.leave.is_none_or(|leave| leave >= s.join)))
+        {
+            return Err(ParamsError::Format(file, format!("invalid session {:?}", session)));
+        }
+        Ok(Availability {
+            file,
+            sessions: Arc::new(sessions),
+        })
+    }
+
+    /// Returns the sessions in the trace
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
+    /// Returns the joins and leaves of the trace in the order of time; at equal times, leaves
+    /// come first
+    pub fn events(&self) -> Vec<AvailabilityEvent> {
+        let mut events: Vec<_> = self.sessions
+            .iter()
+            .flat_map(|s| {
+                let join = AvailabilityEvent {
+                    time: s.join,
+                    id: s.id.clone(),
+                    join: true,
+                };
+                let leave = s.leave.map(|time| AvailabilityEvent {
+                    time,
+                    id: s.id.clone(),
+                    join: false,
+                });
+                Some(join).into_iter().chain(leave)
+            })
+            .collect();
+        events.sort_by(|a, b| {
+            a.time
+                .partial_cmp(&b.time)
+                .unwrap()
+                .then(a.join.cmp(&b.join))
+        });
+        events
+    }
+}
+
+/// Parses `id,join,leave` rows, skipping empty lines, `#` comments and a header line
+fn parse_csv(contents: &str) -> Result<Vec<Session>, String> {
+    let mut sessions = vec![];
+    let mut first_row = true;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split(',').map(str::trim).collect();
+        if fields.len() != 3 {
+            return Err(format!("line {}: expected id,join,leave", i + 1));
+        }
+        let header_allowed = first_row;
+        first_row = false;
+        let join = match fields[1].parse() {
+            Ok(join) => join,
+            Err(_) if header_allowed => continue,
+            Err(_) => return Err(format!("line {}: invalid join time {:?}", i + 1, fields[1])),
+        };
+        let leave = match fields[2] {
+            "" => None,
+            leave => Some(leave
+                .parse()
+                .map_err(|_| format!("line {}: invalid leave time {:?}", i + 1, leave))?),
+        };
+        sessions.push(Session {
+            id: fields[0].to_owned(),
+            join,
+            leave,
+        });
+    }
+    Ok(sessions)
+}
+
+/// Parses an array of objects with `id`, `join` and `leave` fields; the ids can be strings or
+/// numbers
+fn parse_json(contents: &str) -> Result<Vec<Session>, String> {
+    let mut values: Vec<Value> = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    for value in &mut values {
+        if let Some(id) = value.get_mut("id") {
+            if !id.is_string() {
+                *id = Value::String(id.to_string());
+            }
+        }
+    }
+    serde_json::from_value(Value::Array(values)).map_err(|err| err.to_string())
+}
+
+impl FromStr for Availability {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        Availability::load(s)
+    }
+}
+
+impl fmt::Display for Availability {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.file)
+    }
+}
+
+/// Shows the file the trace was loaded from, without the sessions
+impl fmt::Debug for Availability {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{:?}", self.file)
+    }
+}
+
+/// An availability trace as it is serialised: the file it was loaded from and its sessions
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LoadedAvailability {
+    file: String,
+    sessions: Vec<Session>,
+}
+
+impl Serialize for Availability {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut availability = serializer.serialize_struct("Availability", 2)?;
+        availability.serialize_field("file", &self.file)?;
+        availability.serialize_field("sessions", &*self.sessions)?;
+        availability.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Availability {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_loaded(deserializer, |loaded: LoadedAvailability| {
+            Availability::new(loaded.file, loaded.sessions)
+        })
+    }
+}
+
+/// A node of the trace coming online or going offline
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AvailabilityEvent {
+    pub time: f64,
+    pub id: String,
+    /// whether the node comes online, as opposed to going offline
+    pub join: bool,
+}
+
+/// Returns the name a trace id first joins the network with
+fn name_for(id: &str) -> Name {
+    let digest = sha3_256(id.as_bytes());
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    Name(u64::from_be_bytes(bytes))
+}
+
+/// Plays an availability trace into the network, one join or leave per iteration.
+/// The trace ids are mapped to names derived from them, and the names follow the nodes through
+/// relocations, so that a node coming back online rejoins as the same node.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Playback {
+    /// the joins and leaves of the trace
+    events: Vec<AvailabilityEvent>,
+    /// the position of the next event to be played
+    position: usize,
+    /// the current name of every trace id that joined
+    names: BTreeMap<String, Name>,
+    /// the trace id of every current name
+    ids: BTreeMap<Name, String>,
+    /// the trace ids that are online in the trace
+    online: BTreeSet<String>,
+}
+
+impl Playback {
+    /// Starts playing the trace from the beginning and makes the network report relocated
+    /// nodes' new names, so that the ids can follow them
+    pub fn new(network: &mut Network, trace: &Availability) -> Playback {
+        network.set_tracking_renames(true);
+        Playback {
+            events: trace.events(),
+            position: 0,
+            names: BTreeMap::new(),
+            ids: BTreeMap::new(),
+            online: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the time of the last played event
+    pub fn time(&self) -> f64 {
+        self.position
+            .checked_sub(1)
+            .map_or(0.0, |i| self.events[i].time)
+    }
+
+    /// Returns whether all the events of the trace have been played
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.events.len()
+    }
+
+    /// Plays the next event of the trace: a node coming online joins the network for the first
+    /// time, or rejoins it if it was there before, and a node going offline is dropped.
+    /// Events that contradict the trace's own state, like a second join of an online node, are
+    /// skipped.
+    pub fn advance(&mut self, network: &mut Network) {
+        let event = match self.events.get(self.position) {
+            Some(event) => event.clone(),
+            None => return,
+        };
+        self.position += 1;
+        if event.join {
+            if !self.online.insert(event.id.clone()) {
+                return;
+            }
+            let name = match self.names.get(&event.id) {
+                Some(&name) => name,
+                None => {
+                    let name = name_for(&event.id);
+                    let _ = self.names.insert(event.id.clone(), name);
+                    let _ = self.ids.insert(name, event.id.clone());
+                    network.add_node(Node::new(name.0, network.params().init_age));
+                    return;
+                }
+            };
+            if network.has_left(name) {
+                network.rejoin_node(name);
+            } else if !network.has_node(name) {
+                // the network forgets the nodes that leave before becoming adults
+                network.add_node(Node::new(name.0, network.params().init_age));
+            }
+        } else if self.online.remove(&event.id) {
+            let name = self.names[&event.id];
+            if network.has_node(name) {
+                network.drop_node(name);
+            }
+        }
+    }
+
+    /// Moves the ids of the nodes relocated in the last iteration to their new names
+    pub fn follow_renames(&mut self, network: &mut Network) {
+        for (old, new) in network.take_renames() {
+            if let Some(id) = self.ids.remove(&old) {
+                let _ = self.names.insert(id.clone(), new);
+                let _ = self.ids.insert(new, id);
+            }
+        }
+    }
+}
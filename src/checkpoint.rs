@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use serde_json::{self, Value};
+use availability::Playback;
+use clock::Clock;
+use network::Network;
+
+/// The version of the checkpoint format. Checkpoints written with a different version are
+/// refused when loading.
+pub const CHECKPOINT_VERSION: u64 = 1;
+
+/// The checkpoint file contents: the full state of the network and of whatever drives it, tagged
+/// with the format version
+#[derive(Serialize)]
+struct Checkpoint<'a> {
+    version: u64,
+    network: &'a Network,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clock: Option<&'a Clock>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    playback: Option<&'a Playback>,
+}
+
+/// The state of a simulation restored from a checkpoint
+pub struct Restored {
+    pub network: Network,
+    /// the clock of the continuous-time engine
+    pub clock: Option<Clock>,
+    /// the playback of an availability trace
+    pub playback: Option<Playback>,
+}
+
+/// Errors that can happen when saving or loading a checkpoint
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// The checkpoint was written with an unsupported format version
+    Version(Option<u64>),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CheckpointError::Io(ref err) => write!(fmt, "I/O error: {}", err),
+            CheckpointError::Format(ref err) => write!(fmt, "invalid checkpoint: {}", err),
+            CheckpointError::Version(Some(version)) => write!(
+                fmt,
+                "unsupported checkpoint version {} (expected {})",
+                version, CHECKPOINT_VERSION
+            ),
+            CheckpointError::Version(None) => write!(fmt, "checkpoint version missing"),
+        }
+    }
+}
+
+impl Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(err: io::Error) -> Self {
+        CheckpointError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(err: serde_json::Error) -> Self {
+        CheckpointError::Format(err)
+    }
+}
+
+/// Writes the full state of the network, the clock and the playback to a JSON checkpoint file.
+/// The checkpoint is written to `path.tmp` first and then renamed over `path`, so that an
+/// interrupted write never destroys the previous checkpoint.
+pub fn save(
+    network: &Network,
+    clock: Option<&Clock>,
+    playback: Option<&Playback>,
+    path: &str,
+) -> Result<(), CheckpointError> {
+    let tmp_path = format!("{}.tmp", path);
+    let mut file = BufWriter::new(File::create(&tmp_path)?);
+    let checkpoint = Checkpoint {
+        version: CHECKPOINT_VERSION,
+        network,
+        clock,
+        playback,
+    };
+    serde_json::to_writer(&mut file, &checkpoint)?;
+    file.flush()?;
+    file.get_ref().sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Reads the network, the clock and the playback back from a JSON checkpoint file
+pub fn load(path: &str) -> Result<Restored, CheckpointError> {
+    let file = BufReader::new(File::open(path)?);
+    let mut checkpoint: Value = serde_json::from_reader(file)?;
+    match checkpoint.get("version").and_then(Value::as_u64) {
+        Some(CHECKPOINT_VERSION) => (),
+        version => return Err(CheckpointError::Version(version)),
+    }
+    let mut take = |field| {
+        checkpoint
+            .get_mut(field)
+            .map(Value::take)
+            .unwrap_or(Value::Null)
+    };
+    Ok(Restored {
+        network: serde_json::from_value(take("network"))?,
+        clock: serde_json::from_value(take("clock"))?,
+        playback: serde_json::from_value(take("playback"))?,
+    })
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, BTreeSet};
+use network::Network;
+use network::prefix::Name;
+use params::ParamsError;
+use session::unit_open;
+
+/// Returns the join rate at the given time, interpolated linearly between the points of the
+/// schedule and constant outside of it
+pub fn join_rate(schedule: &[(f64, f64)], time: f64) -> f64 {
+    match schedule.iter().position(|&(t, _)| t > time) {
+        None => schedule.last().map_or(0.0, |&(_, rate)| rate),
+        Some(0) => schedule[0].1,
+        Some(i) => {
+            let (t0, r0) = schedule[i - 1];
+            let (t1, r1) = schedule[i];
+            r0 + (r1 - r0) * (time - t0) / (t1 - t0)
+        }
+    }
+}
+
+/// Parses a join rate schedule: either a single rate, or `time:rate` points separated by commas,
+/// in increasing order of time
+pub fn parse_schedule(s: &str) -> Result<Vec<(f64, f64)>, ParamsError> {
+    let invalid = || ParamsError::InvalidValue("join-rate", s.to_owned());
+    let schedule = match s.parse() {
+        Ok(rate) => vec![(0.0, rate)],
+        Err(_) => s
+            .split(',')
+            .map(|point| {
+                let mut parts = point.splitn(2, ':');
+                let time = parts.next().and_then(|t| t.trim().parse().ok());
+                let rate = parts.next().and_then(|r| r.trim().parse().ok());
+                time.and_then(|time| rate.map(|rate| (time, rate)))
+                    .ok_or_else(invalid)
+            })
+            .collect::<Result<_, _>>()?,
+    };
+    if !is_valid_schedule(&schedule) {
+        return Err(invalid());
+    }
+    Ok(schedule)
+}
+
+/// Returns whether the schedule has points in increasing order of time, with finite, non-negative
+/// times and rates
+pub fn is_valid_schedule(schedule: &[(f64, f64)]) -> bool {
+    !schedule.is_empty()
+        && schedule
+            .iter()
+            .all(|&(time, rate)| time >= 0.0 && rate >= 0.0 && time.is_finite() && rate.is_finite())
+        && schedule.windows(2).all(|w| w[0].0 < w[1].0)
+}
+
+/// Returns the maximum join rate of the schedule
+fn max_join_rate(schedule: &[(f64, f64)]) -> f64 {
+    schedule.iter().map(|&(_, rate)| rate).fold(0.0, f64::max)
+}
+
+/// The continuous-time engine: joins arrive as a Poisson process with a time-dependent rate, and
+/// every node leaves when its own session ends.
+/// Times are kept as the bits of non-negative `f64`s, which sort the same as the numbers.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Clock {
+    /// the current simulated time
+    time: f64,
+    /// the time of the next candidate join, if drawn already
+    next_join: Option<f64>,
+    /// the scheduled departures, earliest first
+    departures: BTreeSet<(u64, Name)>,
+    /// the departure time of every node with a scheduled departure
+    sessions: BTreeMap<Name, u64>,
+}
+
+impl Clock {
+    /// Starts the clock at time 0 and makes the network report relocated nodes' new names, so
+    /// that their departures can follow them
+    pub fn new(network: &mut Network) -> Clock {
+        network.set_tracking_renames(true);
+        Clock {
+            time: 0.0,
+            next_join: None,
+            departures: BTreeSet::new(),
+            sessions: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the current simulated time
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Returns whether the simulated duration set in the parameters has passed
+    pub fn is_finished(&self, network: &Network) -> bool {
+        self.time >= network.params().duration
+    }
+
+    /// Draws the time of the next candidate join. Joins with a varying rate are generated by
+    /// thinning a process with the maximum rate of the schedule.
+    fn draw_next_join(&self, network: &mut Network) -> f64 {
+        let max_rate = max_join_rate(&network.params().join_rate);
+        if max_rate <= 0.0 {
+            return f64::INFINITY;
+        }
+        self.time - unit_open(network.rng_mut()).ln() / max_rate
+    }
+
+    /// Advances the clock to the next join or departure and applies it to the network. Candidate
+    /// joins dropped by the thinning only advance the clock. Returns `false` if the duration
+    /// passed before anything happened.
+    pub fn advance(&mut self, network: &mut Network) -> bool {
+        loop {
+            let next_join = match self.next_join {
+                Some(time) => time,
+                None => {
+                    let time = self.draw_next_join(network);
+                    self.next_join = Some(time);
+                    time
+                }
+            };
+            let next_departure = self.departures
+                .iter()
+                .next()
+                .map_or(f64::INFINITY, |&(bits, _)| f64::from_bits(bits));
+            let duration = network.params().duration;
+            if next_join.min(next_departure) > duration {
+                self.time = duration;
+                return false;
+            }
+            if next_join <= next_departure {
+                self.time = next_join;
+                self.next_join = None;
+                let max_rate = max_join_rate(&network.params().join_rate);
+                let rate = join_rate(&network.params().join_rate, self.time);
+                if network.rng_mut().random::<f64>() * max_rate < rate {
+                    let node = network.add_random_node();
+                    let dist = network.params().session.clone();
+                    let session = dist.sample(network.rng_mut());
+                    self.schedule(node.name(), self.time + session);
+                    return true;
+                }
+            } else {
+                let (bits, name) = *self.departures.iter().next().unwrap();
+                let _ = self.departures.remove(&(bits, name));
+                let _ = self.sessions.remove(&name);
+                self.time = f64::from_bits(bits);
+                if network.has_node(name) {
+                    network.drop_node(name);
+                    return true;
+                }
+            }
+        }
+    }
+
+    /// Schedules the departure of a node
+    fn schedule(&mut self, name: Name, time: f64) {
+        let bits = time.to_bits();
+        let _ = self.departures.insert((bits, name));
+        let _ = self.sessions.insert(name, bits);
+    }
+
+    /// Moves the departures of the nodes relocated in the last iteration to their new names
+    pub fn follow_renames(&mut self, network: &mut Network) {
+        for (old, new) in network.take_renames() {
+            if let Some(bits) = self.sessions.remove(&old) {
+                let _ = self.departures.remove(&(bits, old));
+                self.schedule(new, f64::from_bits(bits));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules() {
+        assert_eq!(parse_schedule("2.5").unwrap(), vec![(0.0, 2.5)]);
+        let schedule = parse_schedule("0:1, 100:3,200:0").unwrap();
+        assert_eq!(schedule, vec![(0.0, 1.0), (100.0, 3.0), (200.0, 0.0)]);
+        assert_eq!(join_rate(&schedule, 50.0), 2.0);
+        assert_eq!(join_rate(&schedule, 300.0), 0.0);
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        for s in &["", "x", "-1", "inf", "0:1,", "0:1:2", "0:x", "-1:1", "0:-1", "100:1,0:2", "0:1,0:2"] {
+            assert!(parse_schedule(s).is_err(), "{:?}", s);
+        }
+    }
+}
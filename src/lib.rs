@@ -0,0 +1,35 @@
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate tiny_keccak;
+extern crate toml;
+
+/// Prints a line of the simulation log, unless the parameters say it should be quiet
+macro_rules! log {
+    ($params:expr, $($arg:tt)*) => {
+        if $params.verbose {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub mod availability;
+pub mod checkpoint;
+pub mod clock;
+pub mod network;
+pub mod params;
+pub mod random;
+pub mod replicates;
+pub mod scenario;
+pub mod session;
+pub mod replay;
+pub mod stats;
+pub mod sweep;
+mod simulation;
+
+pub use checkpoint::CheckpointError;
+pub use network::{Network, NetworkStructure, Output};
+pub use params::Params;
+pub use simulation::Simulation;
@@ -1,34 +1,25 @@
+extern crate ageing_sim;
 extern crate clap;
-extern crate rand;
-extern crate serde;
-#[macro_use]
-extern crate serde_derive;
-extern crate serde_json;
-extern crate tiny_keccak;
-
-mod network;
-mod random;
-mod params;
-mod stats;
-
-use random::random_range;
-use network::{Network, NetworkStructure};
-use params::Params;
+
+use ageing_sim::{NetworkStructure, Params, Simulation};
+use ageing_sim::clock::parse_schedule;
+use ageing_sim::params::ParamsError;
+use ageing_sim::network::failure::sort_schedule;
+use ageing_sim::network::lifecycle::write_csv;
+use ageing_sim::network::trace::{write_header, write_records};
+use ageing_sim::replay::replay;
+use ageing_sim::replicates::{Aggregate, Replicate};
+use ageing_sim::random::{parse_seed, seeds};
+use ageing_sim::sweep::{parse_growth, parse_list, parse_numbers, run_parallel, RunSummary, SweepSpec};
 use std::collections::BTreeMap;
-use clap::{App, Arg};
-
-/// Generates a random churn event in the network. There are three possible kinds:
-/// node joining, node leaving and node rejoining.
-fn random_event(network: &mut Network, probs: (u8, u8)) {
-    let x = random_range(0, 100);
-    if x < probs.0 {
-        network.add_random_node();
-    } else if x >= probs.0 && x < probs.0 + probs.1 {
-        network.drop_random_node();
-    } else {
-        network.rejoin_random_node();
-    }
-}
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::process;
+use std::str::FromStr;
+use std::thread;
+use clap::{App, Arg, ArgMatches, SubCommand};
 
 fn print_dist(mut dist: BTreeMap<u8, usize>) {
     let mut age = 1;
@@ -39,9 +30,17 @@ fn print_dist(mut dist: BTreeMap<u8, usize>) {
     }
 }
 
-fn get_params() -> Params {
-    let matches = App::new("Ageing Simulation")
+fn get_matches() -> ArgMatches<'static> {
+    App::new("Ageing Simulation")
         .about("Simulates ageing in SAFE network")
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .help("Loads the parameters from a TOML or JSON file; other options override the values from the file")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("initage")
                 .short("i")
@@ -55,7 +54,7 @@ fn get_params() -> Params {
                 .short("s")
                 .long("split")
                 .value_name("STRATEGY")
-                .help("Selects the strategy for splitting (always/complete); default: complete")
+                .help("Selects the strategy for splitting: always (enough peers)/complete (enough adults)/count (enough nodes of any age)/complete-children (a complete group in each half, no buffer)/buffered (enough adults, with --split-buffers); default: complete")
                 .takes_value(true),
         )
         .arg(
@@ -88,6 +87,13 @@ fn get_params() -> Params {
                 .help("Probability that a peer will be dropped during a step (0-100); default: 7")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("lifecycle_file")
+                .long("lifecycle-out")
+                .value_name("FILE")
+                .help("Output CSV file for the splits and merges: when they were initiated and finalised, how many sections took part and whether they were superseded")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("struct_file")
                 .long("network-struct-out")
@@ -100,7 +106,7 @@ fn get_params() -> Params {
             Arg::with_name("drop_dist")
                 .long("drop-dist")
                 .value_name("DISTR")
-                .help("Drop probability distribution based on the age: exponential(exp)/reverse-proportional(rev) (default: exponential)")
+                .help("Drop probability distribution based on the age: exponential(exp)/reverse-proportional(rev)/uniform/linear:M (decaying from M at age 1 to 1 at age M)/power:K (age^-K)/table:FILE (age and weight on every line) (default: exponential)")
                 .takes_value(true),
         )
         .arg(
@@ -109,107 +115,720 @@ fn get_params() -> Params {
                 .short("a")
                 .help("Increment node ages on merges and splits")
         )
-        .get_matches();
-    let init_age = matches
-        .value_of("initage")
-        .unwrap_or("1")
-        .parse()
-        .expect("Initial age must be a number!");
-    let split = matches
-        .value_of("split")
-        .unwrap_or("complete")
-        .parse()
-        .ok()
-        .expect("Split strategy must be \"always\" or \"complete\".");
-    let drop_dist = matches
-        .value_of("drop_dist")
-        .unwrap_or("exp")
-        .parse()
-        .ok()
-        .expect("Drop distribution must be exp/exponential/rev/reverse-proportional.");
-    let max_young = matches
-        .value_of("max_young")
-        .unwrap_or("1")
-        .parse()
-        .expect("Max number of young peers must be a number!");
-    let iterations = matches
-        .value_of("iterations")
-        .unwrap_or("100000")
-        .parse()
-        .expect("Number of iterations must be a number!");
-    let inc_age = matches.is_present("age_inc");
-    let p_add1 = matches
-        .value_of("p_add1")
-        .unwrap_or("90")
-        .parse()
-        .expect("Add probability must be a number!");
-    assert!(p_add1 < 100, "Probability must be between 0 and 100!");
-    let p_drop1 = matches
-        .value_of("p_drop1")
-        .unwrap_or("7")
-        .parse()
-        .expect("Drop probability must be a number!");
-    assert!(p_drop1 < 100, "Probability must be between 0 and 100!");
-    assert!(
-        p_add1 + p_drop1 <= 100,
-        "Add and drop probabilites must add up to at most 100!"
-    );
-    let structure_output_file = matches.value_of("struct_file").map(|s| s.to_owned());
-    Params {
-        init_age,
-        split_strategy: split,
-        max_young,
-        iterations,
-        growth: (p_add1, p_drop1),
-        structure_output_file,
-        drop_dist,
-        inc_age,
+        .arg(
+            Arg::with_name("group_size")
+                .long("group-size")
+                .value_name("SIZE")
+                .help("Number of Elders in a complete section; default: 8")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("buffer")
+                .long("buffer")
+                .value_name("NODES")
+                .help("Number of nodes above the group size both halves need for a split; default: 3")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("split_buffers")
+                .long("split-buffers")
+                .value_name("NODES")
+                .help("Buffers of the buffered split strategy by the prefix length of the halves, starting with length 1, like '5,4,3'; longer prefixes use the last one; default: the --buffer")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("merge")
+                .long("merge")
+                .value_name("STRATEGY")
+                .help("Selects the strategy for merging: adults (at most a group of adults)/peers (at most a group of peers)/count (at most a group of nodes of any age)/incomplete (too few adults for a complete group); default: the one matching the split strategy")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("min_adult_age")
+                .long("min-adult-age")
+                .value_name("AGE")
+                .help("Minimum age of an Adult; default: 5")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .short("q")
+                .help("Don't log every iteration and event")
+        )
+        .arg(
+            Arg::with_name("malicious")
+                .long("malicious")
+                .value_name("FRACTION")
+                .help("Fraction of joining nodes controlled by an attacker (0-1); default: 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("attacker")
+                .long("attacker")
+                .value_name("NAME")
+                .help("Behaviour of the malicious nodes: none/targeted/rejoin/stubborn; default: none")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("attack_target")
+                .long("attack-target")
+                .value_name("PREFIX")
+                .help("Prefix the targeted attacker tries to get into, as a bit string; default: the section with the most malicious elders")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("grind_budget")
+                .long("grind-budget")
+                .value_name("N")
+                .help("Number of names a joining malicious node tries in order to bias relocations; 0 value means no grinding; default: 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("grind_goal")
+                .long("grind-goal")
+                .value_name("GOAL")
+                .help("What the malicious nodes grind their names for: trigger (relocations of honest nodes)/avoid (relocations); default: trigger")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .long("engine")
+                .value_name("ENGINE")
+                .help("The way the simulation advances: discrete (a fixed number of iterations)/continuous (Poisson joins and per-node sessions in simulated time); default: discrete")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("duration")
+                .long("duration")
+                .value_name("T")
+                .help("Simulated time the continuous engine runs for; default: 10000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("join_rate")
+                .long("join-rate")
+                .value_name("SCHEDULE")
+                .help("Join rate of the continuous engine, as a single rate or 'time:rate' points in increasing order of time, interpolated linearly, like '0:1,5000:3'; default: 1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("session")
+                .long("session")
+                .value_name("DIST")
+                .help("Distribution of the session lengths in the continuous engine and of the true reliabilities: exp:MEAN/weibull:SHAPE:SCALE/pareto:SHAPE:SCALE/lognormal:MU:SIGMA/empirical:FILE (session lengths listed in FILE); default: exp:1000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("availability")
+                .long("availability")
+                .value_name("FILE")
+                .help("Drive the simulation by the joins and leaves of an availability trace instead of the engine: a CSV file with id,join,leave rows or a JSON array of objects with these fields")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("failure")
+                .long("failure")
+                .value_name("AT@FAILURE")
+                .help("Drop many nodes at once at the given iteration (or simulated time, with the continuous engine or an availability trace): PERCENT% (of all nodes, at random)/prefix:BITS/malicious/honest/elders/adults/infants/age:N; can be repeated, e.g. '--failure 5000@30% --failure 8000@prefix:01'")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .value_name("FILE")
+                .help("Run the phases listed in a TOML or JSON scenario file, each with its own duration, growth, drop distribution, one-off events and stop conditions; not with --availability, and without growth or drop_dist in simulated time")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("relocation")
+                .long("relocation")
+                .value_name("POLICY")
+                .help("Section a relocated node moves to: neighbour (with the shortest prefix, then the fewest nodes)/random/hash (of the churn event that triggered the relocation)/smallest (in the network)/fewest-adults (neighbour); default: neighbour")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ageing")
+                .long("ageing")
+                .value_name("RULE")
+                .help("Which node a section relocates after a churn event: hash (no older than the trailing zeros of the event hash + init age)/counter (after 2^age churn events in the section)/probabilistic (with probability 2^-age; traces recorded with it can't be replayed); default: hash")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("elders")
+                .long("elders")
+                .value_name("SELECTION")
+                .help("How a section chooses its Elders among its Adults: oldest (ties by name)/oldest-xor (ties by XOR distance to the prefix)/hysteresis:K (an Elder only gives way to a node K ages older)/weighted (at random, weighted by age); default: oldest")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("true_reliability")
+                .long("true-reliability")
+                .help("Give every node a true reliability drawn from the session distribution when it joins, and drop the nodes by it rather than by their age")
+        )
+        .arg(
+            Arg::with_name("check_invariants")
+                .long("check-invariants")
+                .help("Check the consistency of the network after every iteration and stop with a report on the first violation")
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .help("Seed of the random number generator in the form '[1, 2, 3, 4]'; default: the AGE_SEED environment variable, or random")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("checkpoint_every")
+                .long("checkpoint-every")
+                .value_name("N")
+                .help("Save a checkpoint of the whole network every N iterations; 0 value means never; default: 0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("checkpoint_file")
+                .long("checkpoint-file")
+                .value_name("FILE")
+                .help("File the checkpoints are saved to; default: checkpoint.json")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .value_name("FILE")
+                .help("Write a JSON-lines trace of every network and section event to FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("resume")
+                .long("resume")
+                .value_name("FILE")
+                .help("Resume the simulation from a checkpoint file, with the parameters it was saved with")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Replays a trace recorded with --trace and reports the first divergence")
+                .arg(
+                    Arg::with_name("trace")
+                        .value_name("FILE")
+                        .help("The trace to be replayed")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sweep")
+                .about("Runs the simulation for every combination of the given parameter values and writes one CSV row per run; the other parameters are taken from the main options")
+                .arg(
+                    Arg::with_name("init_age")
+                        .long("init-age")
+                        .value_name("AGES")
+                        .help("Initial ages to sweep over, as a list of numbers and ranges like '1,4..=6'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max_young")
+                        .long("max-young")
+                        .value_name("MAX")
+                        .help("Max numbers of young peers to sweep over, as a list of numbers and ranges")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("split")
+                        .long("split")
+                        .value_name("STRATEGIES")
+                        .help("Split strategies to sweep over, like 'always,complete'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("merge")
+                        .long("merge")
+                        .value_name("STRATEGIES")
+                        .help("Merge strategies to sweep over, like 'adults,incomplete'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("drop_dist")
+                        .long("drop-dist")
+                        .value_name("DISTRS")
+                        .help("Drop distributions to sweep over, like 'exp,rev,uniform,power:2'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("growth")
+                        .long("growth")
+                        .value_name("ADD:DROP")
+                        .help("Pairs of add and drop probabilities to sweep over, like '90:7,80:15'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("inc_age")
+                        .long("inc-age")
+                        .value_name("BOOLS")
+                        .help("Values of the age increment option to sweep over, like 'false,true'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("relocation")
+                        .long("relocation")
+                        .value_name("POLICIES")
+                        .help("Relocation policies to sweep over, like 'neighbour,random,smallest'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("ageing")
+                        .long("ageing")
+                        .value_name("RULES")
+                        .help("Ageing rules to sweep over, like 'hash,counter,probabilistic'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("elders")
+                        .long("elders")
+                        .value_name("SELECTIONS")
+                        .help("Elder selections to sweep over, like 'oldest,hysteresis:2,weighted'")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seeds")
+                        .long("seeds")
+                        .value_name("K")
+                        .help("Number of seeds to run every combination with, which are derived from --seed if given; default: 1")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .long("threads")
+                        .value_name("N")
+                        .help("Number of simulations to run in parallel; default: the number of cores")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .short("o")
+                        .value_name("FILE")
+                        .help("Output CSV file; default: the standard output")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replicates")
+                .about("Runs replicates of the simulation with different seeds and reports the means and 95% confidence intervals of the results; the parameters are taken from the main options")
+                .arg(
+                    Arg::with_name("count")
+                        .long("count")
+                        .short("k")
+                        .value_name("K")
+                        .help("Number of replicates, whose seeds are derived from --seed if given; default: 10")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .long("threads")
+                        .value_name("N")
+                        .help("Number of simulations to run in parallel; default: the number of cores")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .short("o")
+                        .value_name("PREFIX")
+                        .help("Write the results to PREFIX_metrics.csv, PREFIX_age.csv, PREFIX_drops.csv, PREFIX_structure.csv, PREFIX_sections.csv and PREFIX.md")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches()
+}
+
+/// Parses the value of the argument, if present
+fn parse_arg<T: FromStr>(matches: &ArgMatches, arg: &'static str) -> Result<Option<T>, ParamsError> {
+    matches
+        .value_of(arg)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| ParamsError::InvalidValue(arg, value.to_owned()))
+        })
+        .transpose()
+}
+
+/// Loads the parameters from the config file, if any, and overrides them with the values passed
+/// on the command line
+fn get_params(matches: &ArgMatches) -> Result<Params, ParamsError> {
+    let mut params = match matches.value_of("config") {
+        Some(file) => Params::from_file(file)?,
+        None => Params::default(),
+    };
+    if let Some(init_age) = parse_arg(matches, "initage")? {
+        params.init_age = init_age;
+    }
+    if let Some(split) = matches.value_of("split") {
+        params.split_strategy = split.parse()?;
+    }
+    if let Some(drop_dist) = matches.value_of("drop_dist") {
+        params.drop_dist = drop_dist.parse()?;
+    }
+    if let Some(max_young) = parse_arg(matches, "max_young")? {
+        params.max_young = max_young;
+    }
+    if let Some(iterations) = parse_arg(matches, "iterations")? {
+        params.iterations = iterations;
+    }
+    if matches.is_present("age_inc") {
+        params.inc_age = true;
+    }
+    if matches.is_present("quiet") {
+        params.verbose = false;
+    }
+    if let Some(fraction) = parse_arg(matches, "malicious")? {
+        params.malicious_fraction = fraction;
+    }
+    if let Some(attacker) = matches.value_of("attacker") {
+        params.attacker = attacker.parse()?;
+    }
+    if let Some(target) = parse_arg(matches, "attack_target")? {
+        params.attack_target = Some(target);
+    }
+    if let Some(budget) = parse_arg(matches, "grind_budget")? {
+        params.grind_budget = budget;
+    }
+    if let Some(goal) = matches.value_of("grind_goal") {
+        params.grind_goal = goal.parse()?;
+    }
+    if let Some(engine) = matches.value_of("engine") {
+        params.engine = engine.parse()?;
+    }
+    if let Some(duration) = parse_arg(matches, "duration")? {
+        params.duration = duration;
+    }
+    if let Some(schedule) = matches.value_of("join_rate") {
+        params.join_rate = parse_schedule(schedule)?;
+    }
+    if let Some(session) = matches.value_of("session") {
+        params.session = session.parse()?;
+    }
+    if let Some(file) = matches.value_of("availability") {
+        params.availability = Some(file.parse()?);
+    }
+    if let Some(failures) = matches.values_of("failure") {
+        params.failures = failures.map(str::parse).collect::<Result<_, _>>()?;
+        sort_schedule(&mut params.failures);
+    }
+    if let Some(file) = matches.value_of("scenario") {
+        params.scenario = Some(file.parse()?);
+    }
+    if let Some(policy) = matches.value_of("relocation") {
+        params.relocation = policy.parse()?;
+    }
+    if let Some(rule) = matches.value_of("ageing") {
+        params.ageing = rule.parse()?;
+    }
+    if let Some(selection) = matches.value_of("elders") {
+        params.elder_selection = selection.parse()?;
+    }
+    if matches.is_present("true_reliability") {
+        params.true_reliability = true;
+    }
+    if matches.is_present("check_invariants") {
+        params.check_invariants = true;
+    }
+    if let Some(p_add1) = parse_arg(matches, "p_add1")? {
+        params.growth.0 = p_add1;
+    }
+    if let Some(p_drop1) = parse_arg(matches, "p_drop1")? {
+        params.growth.1 = p_drop1;
     }
+    if let Some(group_size) = parse_arg(matches, "group_size")? {
+        params.group_size = group_size;
+    }
+    if let Some(buffer) = parse_arg(matches, "buffer")? {
+        params.buffer = buffer;
+    }
+    if let Some(buffers) = matches.value_of("split_buffers") {
+        params.split_buffers = parse_list("split-buffers", buffers)?;
+    }
+    if let Some(merge) = matches.value_of("merge") {
+        params.merge_strategy = Some(merge.parse()?);
+    }
+    if let Some(min_adult_age) = parse_arg(matches, "min_adult_age")? {
+        params.min_adult_age = min_adult_age;
+    }
+    if let Some(file) = matches.value_of("struct_file") {
+        params.structure_output_file = Some(file.to_owned());
+    }
+    if let Some(file) = matches.value_of("lifecycle_file") {
+        params.lifecycle_output_file = Some(file.to_owned());
+    }
+    let seed = matches
+        .value_of("seed")
+        .map(|s| s.to_owned())
+        .or_else(|| env::var("AGE_SEED").ok());
+    if let Some(seed) = seed {
+        params.seed = Some(parse_seed(&seed).ok_or(ParamsError::InvalidValue("seed", seed))?);
+    }
+    params.validate()?;
+    Ok(params)
 }
 
-fn output_structure_file(file: &str, data: &[NetworkStructure]) {
-    use std::fs::File;
-    use std::io::Write;
+fn output_structure_file(file: &str, params: &Params, data: &[NetworkStructure]) {
     let mut file = File::create(file)
-        .ok()
-        .expect(&format!("Couldn't create file {}!", file));
-    for (i, data) in data.into_iter().enumerate() {
+        .unwrap_or_else(|_| panic!("Couldn't create file {}!", file));
+    for (i, data) in data.iter().enumerate() {
+        let _ = if params.is_timed() {
+            write!(file, "{}", data.time)
+        } else {
+            write!(file, "{}", i)
+        };
         let _ = write!(
             file,
-            "{} {} {} {}\n",
-            i, data.size, data.sections, data.complete
+            " {} {} {} {} {} {}",
+            data.size,
+            data.sections,
+            data.complete,
+            data.third_malicious,
+            data.half_malicious,
+            data.elder_changes
         );
+        if let Some(ref scenario) = params.scenario {
+            let _ = write!(file, " {:?}", scenario.phases()[data.phase].name);
+        }
+        let _ = writeln!(file);
+    }
+}
+
+/// Reports an error to the user and exits
+fn exit_with(err: &dyn Error) -> ! {
+    eprintln!("Error: {}", err);
+    process::exit(1);
+}
+
+fn run_replay(matches: &ArgMatches) {
+    let file = matches.value_of("trace").unwrap();
+    let reader = BufReader::new(File::open(file).unwrap_or_else(|_| panic!("Couldn't open file {}!", file)));
+    let (network, divergence) =
+        replay(reader).unwrap_or_else(|err| panic!("Couldn't replay {}: {}", file, err));
+    println!("Network state:\n{}", network);
+    if let Some(divergence) = divergence {
+        println!("{}", divergence);
+        process::exit(1);
+    }
+    println!(
+        "Replay of {} iterations matches the recording",
+        network.iteration()
+    );
+}
+
+/// Returns the number of threads to run simulations on
+fn get_threads(matches: &ArgMatches) -> Result<usize, ParamsError> {
+    Ok(parse_arg(matches, "threads")?.unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    }))
+}
+
+/// Builds the sweep out of the lists of values passed to the subcommand
+fn get_sweep(matches: &ArgMatches, base: &Params) -> Result<SweepSpec, ParamsError> {
+    let mut spec = SweepSpec::new(base, parse_arg(matches, "seeds")?.unwrap_or(1));
+    if let Some(values) = matches.value_of("init_age") {
+        spec.init_age = parse_numbers("init-age", values)?;
+    }
+    if let Some(values) = matches.value_of("max_young") {
+        spec.max_young = parse_numbers("max-young", values)?;
+    }
+    if let Some(values) = matches.value_of("split") {
+        spec.split_strategy = parse_list("split", values)?;
+    }
+    if let Some(values) = matches.value_of("merge") {
+        spec.merge_strategy = parse_list("merge", values)?.into_iter().map(Some).collect();
+    }
+    if let Some(values) = matches.value_of("drop_dist") {
+        spec.drop_dist = parse_list("drop-dist", values)?;
+    }
+    if let Some(values) = matches.value_of("growth") {
+        spec.growth = parse_growth("growth", values)?;
+    }
+    if let Some(values) = matches.value_of("inc_age") {
+        spec.inc_age = parse_list("inc-age", values)?;
+    }
+    if let Some(values) = matches.value_of("relocation") {
+        spec.relocation = parse_list("relocation", values)?;
+    }
+    if let Some(values) = matches.value_of("ageing") {
+        spec.ageing = parse_list("ageing", values)?;
+    }
+    if let Some(values) = matches.value_of("elders") {
+        spec.elder_selection = parse_list("elders", values)?;
+    }
+    Ok(spec)
+}
+
+fn run_sweep(base: Params, matches: &ArgMatches) {
+    let spec = get_sweep(matches, &base).unwrap_or_else(|err| exit_with(&err));
+    let threads = get_threads(matches).unwrap_or_else(|err| exit_with(&err));
+    let runs = spec.runs(&base);
+    for params in &runs {
+        params.validate().unwrap_or_else(|err| exit_with(&err));
+    }
+    let mut out: Box<dyn Write> = match matches.value_of("out") {
+        Some(file) => {
+            let params_file = format!("{}.params.toml", file);
+            base.save(&params_file).unwrap_or_else(|err| exit_with(&err));
+            Box::new(BufWriter::new(
+                File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file)),
+            ))
+        }
+        None => Box::new(io::stdout()),
+    };
+    let total = runs.len();
+    eprintln!("Running {} simulations on {} threads", total, threads);
+    writeln!(out, "{}", RunSummary::csv_header()).expect("Couldn't write the results!");
+    let mut done = 0;
+    run_parallel(
+        runs,
+        threads,
+        |simulation| RunSummary::new(simulation.network()),
+        |run, summary| {
+            writeln!(out, "{}", summary.csv_row(run)).expect("Couldn't write the results!");
+            done += 1;
+            eprintln!("Finished run {} ({}/{})", run, done, total);
+        },
+    );
+    out.flush().expect("Couldn't write the results!");
+}
+
+fn run_replicates(base: Params, matches: &ArgMatches) {
+    let count = parse_arg(matches, "count")
+        .unwrap_or_else(|err| exit_with(&err))
+        .unwrap_or(10);
+    let threads = get_threads(matches).unwrap_or_else(|err| exit_with(&err));
+    let runs: Vec<Params> = seeds(base.seed, count)
+        .into_iter()
+        .map(|seed| {
+            let mut params = base.clone();
+            params.seed = Some(seed);
+            params.verbose = false;
+            params.structure_output_file = None;
+            params.lifecycle_output_file = None;
+            params
+        })
+        .collect();
+    eprintln!("Running {} replicates on {} threads", count, threads);
+    let mut replicates = vec![];
+    run_parallel(
+        runs,
+        threads,
+        |simulation| Replicate::new(simulation.network()),
+        |run, replicate| {
+            replicates.push(replicate);
+            eprintln!("Finished replicate {} ({}/{})", run, replicates.len(), count);
+        },
+    );
+    let aggregate = Aggregate::new(&replicates);
+    println!("{}", aggregate);
+    if let Some(prefix) = matches.value_of("out") {
+        aggregate
+            .write_csv(prefix)
+            .unwrap_or_else(|err| panic!("Couldn't write the results to {}: {}", prefix, err));
+        let markdown = format!("{}.md", prefix);
+        File::create(&markdown)
+            .and_then(|mut file| write!(file, "{}", aggregate))
+            .unwrap_or_else(|err| panic!("Couldn't write {}: {}", markdown, err));
+        base.save(&format!("{}.params.toml", prefix))
+            .unwrap_or_else(|err| exit_with(&err));
     }
 }
 
 fn main() {
-    let params = get_params();
-    let mut network = Network::new(params.clone());
+    let matches = get_matches();
+    if let Some(matches) = matches.subcommand_matches("replay") {
+        run_replay(matches);
+        return;
+    }
+    if let Some(sweep_matches) = matches.subcommand_matches("sweep") {
+        let base = get_params(&matches).unwrap_or_else(|err| exit_with(&err));
+        run_sweep(base, sweep_matches);
+        return;
+    }
+    if let Some(replicates_matches) = matches.subcommand_matches("replicates") {
+        let base = get_params(&matches).unwrap_or_else(|err| exit_with(&err));
+        run_replicates(base, replicates_matches);
+        return;
+    }
+    let mut simulation = if let Some(file) = matches.value_of("resume") {
+        let mut simulation = Simulation::from_checkpoint(file)
+            .unwrap_or_else(|err| panic!("Couldn't resume from {}: {}", file, err));
+        if let Some(iterations) = parse_arg(&matches, "iterations").unwrap_or_else(|err| exit_with(&err)) {
+            simulation.params_mut().iterations = iterations;
+        }
+        println!("Resuming from iteration {}", simulation.iteration());
+        simulation
+    } else {
+        Simulation::new(get_params(&matches).unwrap_or_else(|err| exit_with(&err)))
+    };
+    println!("Seed: {:?}", simulation.network().seed());
 
-    for i in 0..params.iterations {
-        println!("Iteration {}...", i);
-        // Generate a random event...
-        random_event(&mut network, params.growth);
-        // ... and process the churn cascade that may happen
-        // (every churn event may trigger other churn events, that
-        // may trigger others etc.)
-        network.process_events();
+    let checkpoint_every: usize = matches
+        .value_of("checkpoint_every")
+        .unwrap_or("0")
+        .parse()
+        .expect("Checkpoint interval must be a number!");
+    let checkpoint_file = matches
+        .value_of("checkpoint_file")
+        .unwrap_or("checkpoint.json");
+
+    let mut trace = matches.value_of("trace").map(|file| {
+        simulation.set_tracing(true);
+        let mut trace =
+            BufWriter::new(File::create(file).unwrap_or_else(|_| panic!("Couldn't create file {}!", file)));
+        write_header(&mut trace, simulation.params()).expect("Couldn't write the trace!");
+        trace
+    });
+    // the phases of a scenario change the growth and drop distribution as the run goes
+    let start_params = simulation.params().clone();
+
+    while !simulation.is_finished() {
+        if simulation.params().verbose {
+            println!("Iteration {}...", simulation.iteration());
+        }
+        simulation.step();
+        if let Some(ref mut trace) = trace {
+            write_records(trace, &simulation.take_trace()).expect("Couldn't write the trace!");
+        }
+        if checkpoint_every != 0 && simulation.iteration() % checkpoint_every == 0 {
+            simulation
+                .save_checkpoint(checkpoint_file)
+                .unwrap_or_else(|err| panic!("Couldn't save checkpoint {}: {}", checkpoint_file, err));
+        }
     }
 
-    println!("Network state:\n{}", network);
-    println!("");
+    println!("Network state:\n{}", simulation.network());
+    println!();
 
-    println!("{:?}\n", params.clone());
+    let params = simulation.params().clone();
+    println!("{:?}\n", params);
 
-    let age_dist = network.age_distribution();
+    let age_dist = simulation.age_distribution();
     println!("\nAge distribution:");
     print_dist(age_dist);
 
-    let drop_dist = &network.output().drops_dist;
+    let drop_dist = &simulation.output().drops_dist;
     println!("\nDrops distribution by age:");
     print_dist(drop_dist.clone());
 
     if let Some(ref file) = params.structure_output_file {
-        output_structure_file(file, &network.output().network_structure);
+        output_structure_file(file, &params, &simulation.output().network_structure);
+        // save the parameters next to the results, so that the run can be reproduced
+        let params_file = format!("{}.params.toml", file);
+        start_params.save(&params_file).unwrap_or_else(|err| exit_with(&err));
+    }
+    if let Some(ref file) = params.lifecycle_output_file {
+        File::create(file)
+            .and_then(|f| write_csv(&simulation.output().prefix_changes, BufWriter::new(f)))
+            .unwrap_or_else(|_| panic!("Couldn't write file {}!", file));
     }
 }
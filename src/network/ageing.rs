@@ -0,0 +1,173 @@
+use std::fmt;
+use std::str::FromStr;
+use network::churn::NetworkEvent;
+use network::node::{Digest, Node};
+use network::section::Section;
+use params::{Params, ParamsError};
+use random::SeededRng;
+
+/// The rules for choosing the nodes that age, by being relocated, after a churn event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AgeingKind {
+    /// a node no older than the trailing zeros of the event hash plus the initial age
+    Hash,
+    /// a node that has seen 2^age churn events in the section since it joined it
+    Counter,
+    /// every node with probability 2^-age
+    Probabilistic,
+}
+
+impl AgeingKind {
+    /// Returns whether the rule draws from the random number generator, which makes its choices
+    /// impossible to reproduce from a trace
+    pub fn is_random(self) -> bool {
+        self == AgeingKind::Probabilistic
+    }
+}
+
+impl FromStr for AgeingKind {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        match s {
+            "hash" => Ok(AgeingKind::Hash),
+            "counter" => Ok(AgeingKind::Counter),
+            "probabilistic" => Ok(AgeingKind::Probabilistic),
+            _ => Err(ParamsError::UnknownAgeingRule(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for AgeingKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AgeingKind::Hash => write!(fmt, "hash"),
+            AgeingKind::Counter => write!(fmt, "counter"),
+            AgeingKind::Probabilistic => write!(fmt, "probabilistic"),
+        }
+    }
+}
+
+/// A way of choosing the node a section relocates after a churn event
+pub trait AgeingRule {
+    /// Returns the node the section should relocate after handling the event, updating any
+    /// ageing state the section keeps. `rng` is `None` when the relocation is only being
+    /// predicted, e.g. by an attacker grinding names, in which case rules relying on it can't
+    /// tell.
+    fn relocation(
+        &self,
+        section: &mut Section,
+        event: NetworkEvent,
+        params: &Params,
+        rng: Option<&mut SeededRng>,
+    ) -> Option<Node>;
+}
+
+/// Returns the rule of the given kind
+pub fn ageing_rule(kind: AgeingKind) -> Box<dyn AgeingRule> {
+    match kind {
+        AgeingKind::Hash => Box::new(HashRule),
+        AgeingKind::Counter => Box::new(CounterRule),
+        AgeingKind::Probabilistic => Box::new(ProbabilisticRule),
+    }
+}
+
+/// Returns the number of trailing zeros in a hash
+fn trailing_zeros(hash: Digest) -> u8 {
+    let mut result = 0;
+    let mut byte_index = 31;
+    loop {
+        let zeros = hash[byte_index].trailing_zeros();
+        result += zeros;
+        if zeros < 8 || byte_index == 0 {
+            break;
+        }
+        byte_index -= 1;
+    }
+    result as u8
+}
+
+/// Chooses one of the candidates sorted from the oldest: the oldest one, with ties broken by the
+/// XOR distance of the names to the XOR of all the tied names
+fn choose_oldest(candidates: Vec<Node>) -> Option<Node> {
+    let oldest = candidates.first()?.age();
+    let mut tied: Vec<_> = candidates
+        .into_iter()
+        .filter(|n| n.age() == oldest)
+        .collect();
+    if tied.len() > 1 {
+        let total_xor = tied.iter().fold(0, |total, node| total ^ node.name().0);
+        tied.sort_by_key(|node| node.name().0 ^ total_xor);
+    }
+    tied.first().cloned()
+}
+
+/// Relocates the oldest node no older than the number of trailing zeros in the event hash plus
+/// the initial age
+struct HashRule;
+
+impl AgeingRule for HashRule {
+    fn relocation(
+        &self,
+        section: &mut Section,
+        event: NetworkEvent,
+        params: &Params,
+        _rng: Option<&mut SeededRng>,
+    ) -> Option<Node> {
+        let max_age = trailing_zeros(event.hash()) + params.init_age;
+        choose_oldest(
+            section
+                .nodes_by_age()
+                .into_iter()
+                .filter(|n| n.age() <= max_age)
+                .collect(),
+        )
+    }
+}
+
+/// Counts the churn events every node sees in its section and relocates the oldest node whose
+/// count has reached 2^age. The count starts again in the new section.
+struct CounterRule;
+
+impl AgeingRule for CounterRule {
+    fn relocation(
+        &self,
+        section: &mut Section,
+        _event: NetworkEvent,
+        _params: &Params,
+        _rng: Option<&mut SeededRng>,
+    ) -> Option<Node> {
+        section.count_churn();
+        let due = section
+            .nodes_by_age()
+            .into_iter()
+            .filter(|n| {
+                let threshold = 1u64.checked_shl(n.age() as u32).unwrap_or(u64::MAX);
+                section.churn_count(n.name()) >= threshold
+            })
+            .collect();
+        choose_oldest(due)
+    }
+}
+
+/// Gives every node a chance of 2^-age to be relocated and relocates the oldest of the lucky
+/// ones. Can't be predicted.
+struct ProbabilisticRule;
+
+impl AgeingRule for ProbabilisticRule {
+    fn relocation(
+        &self,
+        section: &mut Section,
+        _event: NetworkEvent,
+        _params: &Params,
+        rng: Option<&mut SeededRng>,
+    ) -> Option<Node> {
+        let rng = rng?;
+        let lucky = section
+            .nodes_by_age()
+            .into_iter()
+            .filter(|n| rng.random::<f64>() < 2.0f64.powi(-(n.age() as i32)))
+            .collect();
+        choose_oldest(lucky)
+    }
+}
@@ -0,0 +1,403 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use network::Network;
+use network::churn::NetworkEvent;
+use network::node::Node;
+use network::prefix::{Name, Prefix};
+use params::ParamsError;
+
+/// The behaviours of the attacker controlling the malicious nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttackerKind {
+    /// the malicious nodes behave like honest ones
+    None,
+    /// drops and rejoins its infants under new names until they land in the target section
+    Targeted,
+    /// rejoins its dropped nodes under the same names, one per iteration, so that they keep most
+    /// of their age and win Elder seats
+    Rejoin,
+    /// refuses to leave when leaving would get one of its nodes relocated
+    Stubborn,
+}
+
+impl FromStr for AttackerKind {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        match s {
+            "none" => Ok(AttackerKind::None),
+            "targeted" => Ok(AttackerKind::Targeted),
+            "rejoin" => Ok(AttackerKind::Rejoin),
+            "stubborn" => Ok(AttackerKind::Stubborn),
+            _ => Err(ParamsError::UnknownAttacker(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for AttackerKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AttackerKind::None => write!(fmt, "none"),
+            AttackerKind::Targeted => write!(fmt, "targeted"),
+            AttackerKind::Rejoin => write!(fmt, "rejoin"),
+            AttackerKind::Stubborn => write!(fmt, "stubborn"),
+        }
+    }
+}
+
+/// What the attacker grinds the names of its joining nodes for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GrindGoal {
+    /// get honest nodes relocated by the joins
+    Trigger,
+    /// keep the joins from relocating anyone
+    Avoid,
+}
+
+impl FromStr for GrindGoal {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        match s {
+            "trigger" => Ok(GrindGoal::Trigger),
+            "avoid" => Ok(GrindGoal::Avoid),
+            _ => Err(ParamsError::UnknownGrindGoal(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for GrindGoal {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GrindGoal::Trigger => write!(fmt, "trigger"),
+            GrindGoal::Avoid => write!(fmt, "avoid"),
+        }
+    }
+}
+
+impl GrindGoal {
+    const BEST_SCORE: u8 = 2;
+
+    /// Rates the relocation a join would cause, the higher the better for the attacker
+    fn score(self, relocated: Option<Node>) -> u8 {
+        match (self, relocated) {
+            (GrindGoal::Trigger, Some(node)) if !node.is_malicious() => 2,
+            (GrindGoal::Trigger, Some(_)) => 1,
+            (GrindGoal::Trigger, None) => 0,
+            (GrindGoal::Avoid, None) => 2,
+            (GrindGoal::Avoid, Some(node)) if !node.is_malicious() => 1,
+            (GrindGoal::Avoid, Some(_)) => 0,
+        }
+    }
+}
+
+/// The relocations caused by joins of honest and malicious nodes, to compare how much grinding
+/// biases them
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct GrindStats {
+    pub honest_joins: u64,
+    /// the relocations triggered by joins of honest nodes
+    pub honest_relocations: u64,
+    pub malicious_joins: u64,
+    /// the relocations triggered by joins of malicious nodes
+    pub malicious_relocations: u64,
+    /// the malicious nodes relocated due to joins of malicious nodes
+    pub own_relocated: u64,
+    /// the number of candidate events the attacker hashed
+    pub hashes: u64,
+}
+
+impl GrindStats {
+    /// Records a join and the node it got relocated, if any
+    pub fn record_join(&mut self, node: Node, relocated: Option<Node>) {
+        if node.is_malicious() {
+            self.malicious_joins += 1;
+            if let Some(relocated) = relocated {
+                self.malicious_relocations += 1;
+                if relocated.is_malicious() {
+                    self.own_relocated += 1;
+                }
+            }
+        } else {
+            self.honest_joins += 1;
+            if relocated.is_some() {
+                self.honest_relocations += 1;
+            }
+        }
+    }
+}
+
+/// Returns the ratio, or 0 if the denominator is 0
+fn ratio(num: u64, den: u64) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
+/// Formats the grinding statistics as rows of the network summary table
+pub struct GrindRows<'a>(pub &'a GrindStats);
+
+impl<'a> fmt::Display for GrindRows<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let stats = self.0;
+        writeln!(fmt, "| Honest joins | {} |", stats.honest_joins)?;
+        writeln!(
+            fmt,
+            "| Relocations per honest join | {:.4} |",
+            ratio(stats.honest_relocations, stats.honest_joins)
+        )?;
+        writeln!(fmt, "| Malicious joins | {} |", stats.malicious_joins)?;
+        writeln!(
+            fmt,
+            "| Relocations per malicious join | {:.4} |",
+            ratio(stats.malicious_relocations, stats.malicious_joins)
+        )?;
+        writeln!(fmt, "| Own nodes relocated | {} |", stats.own_relocated)?;
+        writeln!(fmt, "| Hashes computed | {} |", stats.hashes)
+    }
+}
+
+/// Creates a joining malicious node, choosing its name out of `grind_budget` random candidates
+/// so that the `Live` event it causes serves the attacker's goal best
+pub fn grind_node(network: &mut Network, age: u8) -> Node {
+    let budget = network.params().grind_budget;
+    if budget <= 1 {
+        return Node::new_malicious(network.rng_mut().random(), age);
+    }
+    let goal = network.params().grind_goal;
+    let candidates: Vec<_> = (0..budget)
+        .map(|_| Node::new_malicious(network.rng_mut().random(), age))
+        .collect();
+    let mut best = None;
+    let mut hashes = 0;
+    for node in candidates {
+        hashes += 1;
+        let relocated = network
+            .sections()
+            .iter()
+            .find(|&(prefix, _)| prefix.matches(node.name()))
+            .and_then(|(_, section)| {
+                section.relocation_after(NetworkEvent::Live(node, true), network.params())
+            });
+        let score = goal.score(relocated);
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((node, score));
+        }
+        if score == GrindGoal::BEST_SCORE {
+            break;
+        }
+    }
+    network.output_mut().grinding.hashes += hashes;
+    best.map(|(node, _)| node).unwrap()
+}
+
+/// The attempts of the attacker against a single target section
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct AttackStats {
+    pub attempts: u64,
+    pub successes: u64,
+}
+
+impl AttackStats {
+    /// Returns the fraction of the attempts that succeeded
+    pub fn success_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.successes as f64 / self.attempts as f64
+        }
+    }
+}
+
+/// What a node of the attacker has to achieve for an attempt to succeed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttemptGoal {
+    /// be in the target section
+    #[default]
+    Land,
+    /// be an Elder of the target section
+    Elder,
+}
+
+/// An attempt whose outcome will only be known after the churn it caused has been processed
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PendingAttempt {
+    /// the node that should end up in the target
+    pub name: Name,
+    /// the section the attempt counts against
+    pub target: Prefix,
+    /// what the node has to achieve in the target
+    #[serde(default)]
+    pub goal: AttemptGoal,
+}
+
+/// A behaviour of the malicious nodes. The attackers keep no state of their own: whatever needs
+/// to survive between iterations is kept in the network, so that it's checkpointed with it.
+pub trait Attacker {
+    /// Acts before the random event of every iteration, by applying churn to the network
+    fn act(&self, _network: &mut Network) {}
+
+    /// Decides whether the malicious node is willing to leave its section when chosen to drop
+    fn agrees_to_leave(&self, _network: &mut Network, _prefix: Prefix, _node: Node) -> bool {
+        true
+    }
+}
+
+/// Returns the implementation of the given attacker behaviour
+pub fn attacker(kind: AttackerKind) -> Box<dyn Attacker> {
+    match kind {
+        AttackerKind::None => Box::new(NoAttacker),
+        AttackerKind::Targeted => Box::new(TargetedAttacker),
+        AttackerKind::Rejoin => Box::new(RejoinAttacker),
+        AttackerKind::Stubborn => Box::new(StubbornAttacker),
+    }
+}
+
+/// Settles the pending attempts: an attempt succeeded if its node is in a section matching the
+/// target, and is one of its Elders if that was the goal
+fn settle_attempts(network: &mut Network) {
+    for attempt in network.take_pending_attempts() {
+        let achieved = network
+            .sections()
+            .iter()
+            .filter(|&(prefix, _)| prefix.matches(attempt.name))
+            .any(|(_, section)| match attempt.goal {
+                AttemptGoal::Land => section.nodes().iter().any(|n| n.name() == attempt.name),
+                AttemptGoal::Elder => section.elder_names().contains(&attempt.name),
+            });
+        if achieved && attempt.target.matches(attempt.name) {
+            network.attack_stats_mut(attempt.target).successes += 1;
+        }
+    }
+}
+
+struct NoAttacker;
+
+impl Attacker for NoAttacker {}
+
+struct TargetedAttacker;
+
+impl TargetedAttacker {
+    /// Returns the prefix the attacker wants to get its nodes into: the configured one, or else
+    /// the section in which it already holds the most elders
+    fn target(network: &Network) -> Option<Prefix> {
+        if let Some(target) = network.params().attack_target {
+            return Some(target);
+        }
+        network
+            .sections()
+            .iter()
+            .max_by_key(|&(prefix, section)| (section.malicious_elders(), Reverse(*prefix)))
+            .map(|(prefix, _)| *prefix)
+    }
+}
+
+impl Attacker for TargetedAttacker {
+    fn act(&self, network: &mut Network) {
+        settle_attempts(network);
+        let target = match Self::target(network) {
+            Some(target) => target,
+            None => return,
+        };
+        let min_adult_age = network.params().min_adult_age;
+        let misplaced = network
+            .sections()
+            .values()
+            .flat_map(|s| s.nodes())
+            .find(|n| {
+                n.is_malicious() && !n.is_adult(min_adult_age) && !target.matches(n.name())
+            });
+        if let Some(node) = misplaced {
+            network.drop_node(node.name());
+            let name = network.rng_mut().random();
+            let new_node = Node::new_malicious(name, network.params().init_age);
+            network.add_node(new_node);
+            network.attack_stats_mut(target).attempts += 1;
+            network.push_pending_attempt(PendingAttempt {
+                name: new_node.name(),
+                target,
+                goal: AttemptGoal::Land,
+            });
+        }
+    }
+}
+
+/// Rejoins the oldest of its nodes that left, once per iteration, which adds a churn event of its
+/// own to every iteration while there are any. A rejoin counts as an attempt against the section
+/// the node rejoins, and as a success if the node gets an Elder seat there.
+struct RejoinAttacker;
+
+impl Attacker for RejoinAttacker {
+    fn act(&self, network: &mut Network) {
+        settle_attempts(network);
+        let oldest = network
+            .left_nodes()
+            .iter()
+            .filter(|n| n.is_malicious())
+            .max_by_key(|n| n.age())
+            .map(|n| n.name());
+        if let Some(name) = oldest {
+            let target = network
+                .sections()
+                .keys()
+                .find(|prefix| prefix.matches(name))
+                .cloned()
+                .unwrap_or_else(Prefix::empty);
+            network.rejoin_node(name);
+            network.attack_stats_mut(target).attempts += 1;
+            network.push_pending_attempt(PendingAttempt {
+                name,
+                target,
+                goal: AttemptGoal::Elder,
+            });
+        }
+    }
+}
+
+struct StubbornAttacker;
+
+impl Attacker for StubbornAttacker {
+    /// Every malicious node chosen to drop counts as an attempt, and every refusal to leave as a
+    /// success
+    fn agrees_to_leave(&self, network: &mut Network, prefix: Prefix, node: Node) -> bool {
+        let relocated = network
+            .sections()
+            .get(&prefix)
+            .and_then(|section| {
+                section.relocation_after(NetworkEvent::Lost(node.name()), network.params())
+            });
+        let refuses = relocated.is_some_and(|relocated| relocated.is_malicious());
+        let stats = network.attack_stats_mut(prefix);
+        stats.attempts += 1;
+        if refuses {
+            stats.successes += 1;
+        }
+        !refuses
+    }
+}
+
+/// Formats the attack statistics as a markdown table
+pub struct AttackTable<'a>(pub &'a BTreeMap<Prefix, AttackStats>);
+
+impl<'a> fmt::Display for AttackTable<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "| Target | Attempts | Successes | Success rate |")?;
+        writeln!(fmt, "|:-------|---------:|----------:|-------------:|")?;
+        for (prefix, stats) in self.0 {
+            writeln!(
+                fmt,
+                "| {:?} | {} | {} | {:.4} |",
+                prefix,
+                stats.attempts,
+                stats.successes,
+                stats.success_rate()
+            )?;
+        }
+        Ok(())
+    }
+}
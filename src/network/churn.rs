@@ -7,7 +7,7 @@ use tiny_keccak::sha3_256;
 /// The sections handle them and generate new ones
 /// in the process. Some events can also be generated from
 /// the outside.
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NetworkEvent {
     // Boolean parameter indicates if event should count for node ageing.
     // It is true except for the specific case of a Live event generated during a merge operation
@@ -29,10 +29,10 @@ impl NetworkEvent {
     /// This function determines whether an event should count towards
     /// churn in ageing peers in the section. Currently true for all events.
     pub fn should_count(&self) -> bool {
-        match *self {
-            NetworkEvent::StartMerge(_) | NetworkEvent::Gone(_) | NetworkEvent::Live(_, false) => false,
-            _ => true,
-        }
+        !matches!(
+            *self,
+            NetworkEvent::StartMerge(_) | NetworkEvent::Gone(_) | NetworkEvent::Live(_, false)
+        )
     }
 }
 
@@ -40,7 +40,7 @@ impl NetworkEvent {
 /// The network processes them and responds with churn
 /// events that the nodes would add to their data chains
 /// in the real network.
-#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SectionEvent {
     NodeDropped(Node),
     NodeRejected(Node),
@@ -48,3 +48,15 @@ pub enum SectionEvent {
     RequestMerge,
     RequestSplit,
 }
+
+/// Churn generated from the outside of the network: nodes joining, leaving and rejoining.
+/// These are the inputs of the simulation - everything else follows from them.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ExternalEvent {
+    /// a new node joins the network
+    Add(Node),
+    /// the node with the given name leaves the network
+    Drop(Name),
+    /// the node with the given name, which left the network before, rejoins
+    Rejoin(Name),
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use tiny_keccak::sha3_256;
+use network::node::Node;
+use network::prefix::{prefix_map, Name, Prefix};
+use network::section::Section;
+use params::{Params, ParamsError};
+
+/// The rules for choosing the Elders of a section among its Adults.
+/// Written as `oldest`, `oldest-xor`, `hysteresis:K` or `weighted`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElderKind {
+    /// the oldest Adults, with ties between equally old ones broken by name order
+    Oldest,
+    /// the oldest Adults, with ties broken by the XOR distance to the section's prefix
+    OldestXor,
+    /// the oldest Adults, but a sitting Elder only loses its seat to a node at least the given
+    /// number of ages older
+    Hysteresis(u8),
+    /// Adults chosen at random with weights proportional to their ages
+    Weighted,
+}
+
+impl FromStr for ElderKind {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        if let Some(k) = s.strip_prefix("hysteresis:") {
+            return k
+                .parse()
+                .ok()
+                .filter(|&k| k > 0)
+                .map(ElderKind::Hysteresis)
+                .ok_or_else(|| ParamsError::InvalidValue("elder selection", s.to_owned()));
+        }
+        match s {
+            "oldest" => Ok(ElderKind::Oldest),
+            "oldest-xor" => Ok(ElderKind::OldestXor),
+            "weighted" => Ok(ElderKind::Weighted),
+            _ => Err(ParamsError::UnknownElderSelection(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for ElderKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ElderKind::Oldest => write!(fmt, "oldest"),
+            ElderKind::OldestXor => write!(fmt, "oldest-xor"),
+            ElderKind::Hysteresis(k) => write!(fmt, "hysteresis:{}", k),
+            ElderKind::Weighted => write!(fmt, "weighted"),
+        }
+    }
+}
+
+impl Serialize for ElderKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ElderKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|err: ParamsError| D::Error::custom(err.to_string()))
+    }
+}
+
+/// A way of choosing the Elders of a section
+pub trait ElderSelection {
+    /// Returns the names of the nodes that should be the Elders of the section. The current
+    /// Elders are still set in the section.
+    fn choose(&self, section: &Section, params: &Params) -> BTreeSet<Name>;
+}
+
+/// Returns the selection of the given kind
+pub fn elder_selection(kind: ElderKind) -> Box<dyn ElderSelection> {
+    match kind {
+        ElderKind::Oldest => Box::new(OldestSelection),
+        ElderKind::OldestXor => Box::new(OldestXorSelection),
+        ElderKind::Hysteresis(k) => Box::new(HysteresisSelection(k)),
+        ElderKind::Weighted => Box::new(WeightedSelection),
+    }
+}
+
+/// Returns the Adults of the section, from the oldest; equally old ones are ordered by the XOR
+/// distance of their names to the section's prefix
+fn adults_by_age_and_xor(section: &Section, params: &Params) -> Vec<Node> {
+    let target = section.prefix().substituted_in(Name(0)).0;
+    let mut adults: Vec<_> = section
+        .nodes()
+        .into_iter()
+        .filter(|n| n.is_adult(params.min_adult_age))
+        .collect();
+    adults.sort_by_key(|n| (-(n.age() as i16), n.name().0 ^ target));
+    adults
+}
+
+/// The `group_size` oldest nodes, as long as they are Adults; this is the way Elders have always
+/// been chosen
+struct OldestSelection;
+
+impl ElderSelection for OldestSelection {
+    fn choose(&self, section: &Section, params: &Params) -> BTreeSet<Name> {
+        section
+            .nodes_by_age()
+            .into_iter()
+            .take(params.group_size)
+            .filter(|n| n.is_adult(params.min_adult_age))
+            .map(|n| n.name())
+            .collect()
+    }
+}
+
+/// The `group_size` oldest Adults, with a tie-break that doesn't depend on how the nodes happen
+/// to be stored
+struct OldestXorSelection;
+
+impl ElderSelection for OldestXorSelection {
+    fn choose(&self, section: &Section, params: &Params) -> BTreeSet<Name> {
+        adults_by_age_and_xor(section, params)
+            .into_iter()
+            .take(params.group_size)
+            .map(|n| n.name())
+            .collect()
+    }
+}
+
+/// Keeps the sitting Elders that are still Adults and fills the free seats with the oldest
+/// Adults. Then, as long as some node is at least `k` ages older than the youngest Elder, the
+/// two swap places.
+struct HysteresisSelection(u8);
+
+impl ElderSelection for HysteresisSelection {
+    fn choose(&self, section: &Section, params: &Params) -> BTreeSet<Name> {
+        let sitting = section.elder_names();
+        let (mut elders, mut others): (Vec<_>, Vec<_>) = adults_by_age_and_xor(section, params)
+            .into_iter()
+            .partition(|n| sitting.contains(&n.name()));
+        while elders.len() < params.group_size && !others.is_empty() {
+            elders.push(others.remove(0));
+        }
+        // both lists stay sorted from the oldest, so it's enough to compare their ends
+        while let (Some(youngest), Some(oldest)) = (elders.last().cloned(), others.first().cloned()) {
+            if oldest.age() < youngest.age().saturating_add(self.0) {
+                break;
+            }
+            let _ = elders.pop();
+            let _ = others.remove(0);
+            let pos = elders
+                .iter()
+                .position(|n| n.age() < oldest.age())
+                .unwrap_or(elders.len());
+            elders.insert(pos, oldest);
+            let pos = others
+                .iter()
+                .position(|n| n.age() < youngest.age())
+                .unwrap_or(others.len());
+            others.insert(pos, youngest);
+        }
+        elders.into_iter().map(|n| n.name()).collect()
+    }
+}
+
+/// Draws `group_size` Adults without replacement, with probabilities proportional to their ages.
+/// The random numbers come from hashing the names together with the section's prefix, so that
+/// anyone can verify the choice and it only changes when the section does.
+struct WeightedSelection;
+
+impl ElderSelection for WeightedSelection {
+    fn choose(&self, section: &Section, params: &Params) -> BTreeSet<Name> {
+        let prefix = section.prefix();
+        let mut keyed: Vec<_> = section
+            .nodes()
+            .into_iter()
+            .filter(|n| n.is_adult(params.min_adult_age))
+            .map(|n| (weighted_key(n, prefix), n.name()))
+            .collect();
+        // the largest keys win (Efraimidis-Spirakis sampling)
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.cmp(&b.1)));
+        keyed
+            .into_iter()
+            .take(params.group_size)
+            .map(|(_, name)| name)
+            .collect()
+    }
+}
+
+/// Returns the sampling key of a node: `ln(u) / age` for `u` from (0, 1] derived from the hash of
+/// the name and the prefix
+fn weighted_key(node: Node, prefix: Prefix) -> f64 {
+    let digest = sha3_256(format!("{}/{}", node.name().0, prefix).as_bytes());
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    let u = ((u64::from_be_bytes(bytes) >> 11) + 1) as f64 / (1u64 << 53) as f64;
+    u.ln() / (node.age() as f64).max(1.0)
+}
+
+/// The changes of the sections' Elders caused by churn, counted once per section and iteration.
+/// Every change costs the real network a round of distributed key generation (DKG) and a
+/// rotation of the section keys.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ElderStats {
+    /// the number of times the Elders of a section changed
+    pub changes: u64,
+    /// the number of nodes that became Elders in those changes
+    pub swapped: u64,
+    /// the number of changes by the number of nodes that became Elders in them
+    #[serde(default)]
+    pub swap_sizes: BTreeMap<usize, u64>,
+    /// the number of changes by section
+    #[serde(default, with = "prefix_map")]
+    pub by_section: BTreeMap<Prefix, u64>,
+    /// the number of changes in the current iteration
+    #[serde(default)]
+    pub iteration_changes: u64,
+    /// the largest number of changes in a single iteration
+    #[serde(default)]
+    pub max_iteration_changes: u64,
+}
+
+impl ElderStats {
+    /// Records a change of the Elders of the section with the given prefix from `old` to `new`,
+    /// if they differ
+    pub fn record(&mut self, prefix: Prefix, old: &BTreeSet<Name>, new: &BTreeSet<Name>) {
+        if old == new {
+            return;
+        }
+        let swapped = new.difference(old).count();
+        self.changes += 1;
+        self.swapped += swapped as u64;
+        *self.swap_sizes.entry(swapped).or_insert(0) += 1;
+        *self.by_section.entry(prefix).or_insert(0) += 1;
+        self.iteration_changes += 1;
+    }
+
+    /// Ends the current iteration and returns the number of changes in it
+    pub fn end_iteration(&mut self) -> u64 {
+        let changes = mem::replace(&mut self.iteration_changes, 0);
+        self.max_iteration_changes = self.max_iteration_changes.max(changes);
+        changes
+    }
+
+    /// Returns the number of nodes that became Elders per churn event
+    pub fn turnover(&self, churn: u64) -> Option<f64> {
+        if churn == 0 {
+            None
+        } else {
+            Some(self.swapped as f64 / churn as f64)
+        }
+    }
+
+    /// Returns the number of DKG rounds per 1000 churn events
+    pub fn dkg_per_1000(&self, churn: u64) -> Option<f64> {
+        if churn == 0 {
+            None
+        } else {
+            Some(self.changes as f64 * 1000.0 / churn as f64)
+        }
+    }
+}
+
+/// Formats the elder change statistics as rows of the summary table, given the total churn
+pub struct ElderRows<'a>(pub &'a ElderStats, pub u64);
+
+impl<'a> fmt::Display for ElderRows<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let ElderRows(stats, churn) = *self;
+        if let Some(turnover) = stats.turnover(churn) {
+            writeln!(fmt, "| Elder turnover per churn event | {:.3} |", turnover)?;
+        }
+        writeln!(fmt, "| DKG rounds | {} |", stats.changes)?;
+        if let Some(rounds) = stats.dkg_per_1000(churn) {
+            writeln!(fmt, "| DKG rounds per 1000 churn events | {:.2} |", rounds)?;
+        }
+        if stats.changes > 0 {
+            writeln!(
+                fmt,
+                "| Elders swapped per DKG round | {:.2} |",
+                stats.swapped as f64 / stats.changes as f64
+            )?;
+        }
+        writeln!(fmt, "| Max DKG rounds in an iteration | {} |", stats.max_iteration_changes)?;
+        if let Some((prefix, rounds)) = stats.by_section.iter().max_by_key(|&(_, &rounds)| rounds) {
+            writeln!(fmt, "| Max DKG rounds in a section | {} ({:?}) |", rounds, prefix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Displays the numbers of DKG rounds by the number of Elders swapped in them as a markdown table
+pub struct SwapTable<'a>(pub &'a ElderStats);
+
+impl<'a> fmt::Display for SwapTable<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "| Elders swapped | DKG rounds |")?;
+        writeln!(fmt, "|---------------:|-----------:|")?;
+        for (swapped, rounds) in &self.0.swap_sizes {
+            writeln!(fmt, "| {} | {} |", swapped, rounds)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use network::prefix::Prefix;
+use params::ParamsError;
+
+/// A property shared by the nodes dropped in a mass failure
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attribute {
+    /// the nodes controlled by an attacker
+    Malicious,
+    /// the nodes not controlled by an attacker
+    Honest,
+    /// the elders of every section
+    Elders,
+    /// the nodes old enough to be adults
+    Adults,
+    /// the nodes too young to be adults
+    Infants,
+    /// the nodes of the given age
+    Age(u8),
+}
+
+/// A catastrophic event dropping many nodes at once
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    /// the given percentage of all nodes, chosen at random
+    Percent(f64),
+    /// every node whose name falls under the prefix, e.g. a partitioned part of the name space
+    Prefix(Prefix),
+    /// every node with the attribute
+    Attribute(Attribute),
+}
+
+impl FromStr for Failure {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        let invalid = || ParamsError::InvalidValue("failure", s.to_owned());
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent: f64 = percent.parse().map_err(|_| invalid())?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(invalid());
+            }
+            return Ok(Failure::Percent(percent));
+        }
+        if let Some(prefix) = s.strip_prefix("prefix:") {
+            return prefix.parse().map(Failure::Prefix).map_err(|_| invalid());
+        }
+        if let Some(age) = s.strip_prefix("age:") {
+            return age
+                .parse()
+                .map(|age| Failure::Attribute(Attribute::Age(age)))
+                .map_err(|_| invalid());
+        }
+        let attribute = match s {
+            "malicious" => Attribute::Malicious,
+            "honest" => Attribute::Honest,
+            "elders" => Attribute::Elders,
+            "adults" => Attribute::Adults,
+            "infants" => Attribute::Infants,
+            _ => return Err(invalid()),
+        };
+        Ok(Failure::Attribute(attribute))
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Failure::Percent(percent) => write!(fmt, "{}%", percent),
+            Failure::Prefix(ref prefix) => write!(fmt, "prefix:{}", prefix),
+            Failure::Attribute(Attribute::Malicious) => write!(fmt, "malicious"),
+            Failure::Attribute(Attribute::Honest) => write!(fmt, "honest"),
+            Failure::Attribute(Attribute::Elders) => write!(fmt, "elders"),
+            Failure::Attribute(Attribute::Adults) => write!(fmt, "adults"),
+            Failure::Attribute(Attribute::Infants) => write!(fmt, "infants"),
+            Failure::Attribute(Attribute::Age(age)) => write!(fmt, "age:{}", age),
+        }
+    }
+}
+
+/// A failure set to happen at a given point of the simulation.
+/// Written as `AT@FAILURE`, e.g. `5000@30%`, `5000@prefix:01` or `5000@elders`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScheduledFailure {
+    /// the iteration, or the simulated time if the simulation runs in time
+    pub at: f64,
+    pub failure: Failure,
+}
+
+/// Sorts the failures by the point at which they happen, keeping the order of the simultaneous
+/// ones
+pub fn sort_schedule(failures: &mut [ScheduledFailure]) {
+    failures.sort_by(|a, b| a.at.partial_cmp(&b.at).unwrap());
+}
+
+impl FromStr for ScheduledFailure {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        let mut parts = s.splitn(2, '@');
+        let at = parts
+            .next()
+            .and_then(|at| at.parse().ok())
+            .filter(|&at: &f64| at >= 0.0)
+            .ok_or_else(|| ParamsError::InvalidValue("failure", s.to_owned()))?;
+        let failure = parts
+            .next()
+            .ok_or_else(|| ParamsError::InvalidValue("failure", s.to_owned()))?
+            .parse()?;
+        Ok(ScheduledFailure { at, failure })
+    }
+}
+
+impl fmt::Display for ScheduledFailure {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}@{}", self.at, self.failure)
+    }
+}
+
+impl Serialize for ScheduledFailure {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ScheduledFailure {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|err: ParamsError| D::Error::custom(err.to_string()))
+    }
+}
+
+/// The damage done by a failure and the recovery of the network from it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FailureReport {
+    /// the failure, as it was written
+    pub failure: String,
+    /// the iteration in which it happened
+    pub iteration: usize,
+    /// the simulated time at which it happened
+    pub time: f64,
+    /// the number of nodes dropped
+    pub dropped: usize,
+    /// the number of complete sections right before the failure
+    pub complete_before: usize,
+    /// the number of complete sections left with fewer adults than a complete group
+    pub lost_complete: usize,
+    /// the number of merges finalised from the failure until the recovery
+    pub merges: usize,
+    /// the number of iterations it took until there were as many complete sections as before
+    pub recovery_iterations: Option<usize>,
+    /// the simulated time it took until there were as many complete sections as before
+    pub recovery_time: Option<f64>,
+}
+
+impl FailureReport {
+    /// Returns whether the network hasn't recovered from the failure yet
+    pub fn is_recovering(&self) -> bool {
+        self.recovery_iterations.is_none()
+    }
+}
+
+/// Displays the failure reports as a markdown table
+pub struct FailureTable<'a>(pub &'a [FailureReport], pub bool);
+
+impl<'a> fmt::Display for FailureTable<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let FailureTable(reports, timed) = *self;
+        write!(
+            fmt,
+            "| Iteration | Failure | Dropped | Complete before | Lost complete groups | Merges | Recovery iterations |"
+        )?;
+        if timed {
+            write!(fmt, " Time | Recovery time |")?;
+        }
+        writeln!(fmt)?;
+        write!(fmt, "|----------:|:--------|--------:|----------------:|---------------------:|-------:|--------------------:|")?;
+        if timed {
+            write!(fmt, "-----:|--------------:|")?;
+        }
+        writeln!(fmt)?;
+        for report in reports {
+            write!(
+                fmt,
+                "| {} | {} | {} | {} | {} | {} | {} |",
+                report.iteration,
+                report.failure,
+                report.dropped,
+                report.complete_before,
+                report.lost_complete,
+                report.merges,
+                report
+                    .recovery_iterations
+                    .map_or("never".to_owned(), |i| i.to_string())
+            )?;
+            if timed {
+                write!(
+                    fmt,
+                    " {:.2} | {} |",
+                    report.time,
+                    report
+                        .recovery_time
+                        .map_or("never".to_owned(), |t| format!("{:.2}", t))
+                )?;
+            }
+            writeln!(fmt)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for s in &[
+            "30%",
+            "prefix:01",
+            "malicious",
+            "honest",
+            "elders",
+            "adults",
+            "infants",
+            "age:7",
+        ] {
+            let failure: Failure = s.parse().unwrap();
+            assert_eq!(failure.to_string(), *s);
+        }
+        let scheduled: ScheduledFailure = "5000@prefix:01".parse().unwrap();
+        assert_eq!(scheduled.at, 5000.0);
+        assert_eq!(scheduled.to_string(), "5000@prefix:01");
+    }
+
+    #[test]
+    fn invalid_failures() {
+        for s in &["", "x", "101%", "-1%", "x%", "prefix:2", "age:256", "age:x"] {
+            assert!(s.parse::<Failure>().is_err(), "{:?}", s);
+        }
+        for s in &["elders", "@elders", "-1@elders", "x@elders", "5000@x"] {
+            assert!(s.parse::<ScheduledFailure>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn sorted_schedule() {
+        let mut failures: Vec<ScheduledFailure> = ["20@elders", "10@30%", "20@honest"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        sort_schedule(&mut failures);
+        let sorted: Vec<_> = failures.iter().map(|f| f.to_string()).collect();
+        assert_eq!(sorted, vec!["10@30%", "20@elders", "20@honest"]);
+    }
+}
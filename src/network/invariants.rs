@@ -0,0 +1,71 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use network::node::Node;
+use network::prefix::{Name, Prefix};
+
+/// The number of most recent trace records kept for the report of a violated invariant
+pub const RECENT_EVENTS: usize = 100;
+
+/// A broken consistency rule of the network state
+#[derive(Clone, Debug)]
+pub enum Violation {
+    /// the prefixes of the sections overlap
+    Overlap(Prefix, Prefix),
+    /// the prefixes of the sections don't cover the whole name space
+    Coverage,
+    /// a node doesn't match the verifying prefix of its section
+    ForeignNode(Prefix, Node),
+    /// the adults or infants of a section don't agree with its nodes: (set, expected, actual)
+    Categories(Prefix, &'static str, BTreeSet<Name>, BTreeSet<Name>),
+    /// the elders of a section aren't the ones `update_elders` would choose: (expected, actual)
+    Elders(Prefix, BTreeSet<Name>, BTreeSet<Name>),
+    /// a node is in two sections at once
+    Duplicate(Name, Prefix, Prefix),
+    /// a node is both in a section and among the nodes that left the network
+    LeftAndLive(Name, Prefix),
+}
+
+impl Violation {
+    /// Returns the prefix of the offending section, if there is one
+    pub fn prefix(&self) -> Option<Prefix> {
+        match *self {
+            Violation::Overlap(prefix, _)
+            | Violation::ForeignNode(prefix, _)
+            | Violation::Categories(prefix, ..)
+            | Violation::Elders(prefix, ..)
+            | Violation::Duplicate(_, prefix, _)
+            | Violation::LeftAndLive(_, prefix) => Some(prefix),
+            Violation::Coverage => None,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::Overlap(ref a, ref b) => write!(fmt, "sections {:?} and {:?} overlap", a, b),
+            Violation::Coverage => write!(fmt, "the sections don't cover the whole name space"),
+            Violation::ForeignNode(ref prefix, ref node) => {
+                write!(fmt, "{:?} doesn't match the section {:?}", node, prefix)
+            }
+            Violation::Categories(ref prefix, set, ref expected, ref actual) => write!(
+                fmt,
+                "the {} of section {:?} are {:?}, expected {:?}",
+                set, prefix, actual, expected
+            ),
+            Violation::Elders(ref prefix, ref expected, ref actual) => write!(
+                fmt,
+                "the elders of section {:?} are {:?}, expected {:?}",
+                prefix, actual, expected
+            ),
+            Violation::Duplicate(ref name, ref a, ref b) => {
+                write!(fmt, "{:?} is in both sections {:?} and {:?}", name, a, b)
+            }
+            Violation::LeftAndLive(ref name, ref prefix) => write!(
+                fmt,
+                "{:?} is in section {:?} and among the left nodes",
+                name, prefix
+            ),
+        }
+    }
+}
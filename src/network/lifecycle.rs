@@ -0,0 +1,109 @@
+use std::fmt;
+use std::io::{self, Write};
+use network::prefix::Prefix;
+
+/// The kind of a change of the sections' prefixes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Split,
+    Merge,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChangeKind::Split => write!(fmt, "split"),
+            ChangeKind::Merge => write!(fmt, "merge"),
+        }
+    }
+}
+
+/// The course of a single split or merge
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    pub kind: ChangeKind,
+    /// the prefix of the splitting section, or the prefix the sections merge into
+    pub prefix: Prefix,
+    /// the iteration in which the change was requested
+    pub initiated: usize,
+    /// the iteration in which the change took effect, if it did
+    pub finalised: Option<usize>,
+    /// the number of sections taking part
+    pub participants: usize,
+    /// whether the change was dropped in favour of another one
+    pub superseded: bool,
+}
+
+impl ChangeRecord {
+    /// Creates the record of a change initiated in the given iteration
+    pub fn new(kind: ChangeKind, prefix: Prefix, initiated: usize, participants: usize) -> ChangeRecord {
+        ChangeRecord {
+            kind,
+            prefix,
+            initiated,
+            finalised: None,
+            participants,
+            superseded: false,
+        }
+    }
+
+    /// Returns whether the change is still under way
+    pub fn is_pending(&self) -> bool {
+        self.finalised.is_none() && !self.superseded
+    }
+}
+
+/// Writes the records as CSV, with a header line
+pub fn write_csv<W: Write>(records: &[ChangeRecord], mut out: W) -> io::Result<()> {
+    writeln!(out, "kind,prefix,initiated,finalised,participants,superseded")?;
+    for record in records {
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            record.kind,
+            record.prefix,
+            record.initiated,
+            record.finalised.map(|i| i.to_string()).unwrap_or_default(),
+            record.participants,
+            record.superseded
+        )?;
+    }
+    Ok(())
+}
+
+/// Displays a summary of the splits and merges as a markdown table
+pub struct LifecycleTable<'a>(pub &'a [ChangeRecord]);
+
+impl<'a> fmt::Display for LifecycleTable<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "| Change | Initiated | Finalised | Superseded | Pending | Mean iterations | Mean participants |")?;
+        writeln!(fmt, "|:-------|----------:|----------:|-----------:|--------:|----------------:|------------------:|")?;
+        for &kind in &[ChangeKind::Split, ChangeKind::Merge] {
+            let records: Vec<_> = self.0.iter().filter(|r| r.kind == kind).collect();
+            let finalised: Vec<_> = records
+                .iter()
+                .filter_map(|r| r.finalised.map(|end| (end - r.initiated, r.participants)))
+                .collect();
+            let mean = |values: Vec<usize>| {
+                if values.is_empty() {
+                    "-".to_owned()
+                } else {
+                    format!("{:.2}", values.iter().sum::<usize>() as f64 / values.len() as f64)
+                }
+            };
+            writeln!(
+                fmt,
+                "| {} | {} | {} | {} | {} | {} | {} |",
+                kind,
+                records.len(),
+                finalised.len(),
+                records.iter().filter(|r| r.superseded).count(),
+                records.iter().filter(|r| r.is_pending()).count(),
+                mean(finalised.iter().map(|&(duration, _)| duration).collect()),
+                mean(finalised.iter().map(|&(_, participants)| participants).collect())
+            )?;
+        }
+        Ok(())
+    }
+}
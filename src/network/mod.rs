@@ -1,15 +1,27 @@
+pub mod ageing;
+pub mod attack;
 pub mod churn;
+pub mod elders;
+pub mod failure;
+pub mod invariants;
+pub mod lifecycle;
 pub mod prefix;
 pub mod node;
+#[allow(clippy::module_inception)]
 pub mod network;
+pub mod relocation;
 pub mod section;
+pub mod split;
+pub mod trace;
 
-/// Determines the numbers of the elders in every section
+/// The default number of the elders in every section
 pub const GROUP_SIZE: usize = 8;
-/// A number of spare nodes when splitting - we don't want to
+/// The default number of spare nodes when splitting - we don't want to
 /// merge again right after we split if a node leaves, so we
 /// only split if the child sections will have at least
 /// GROUP_SIZE + BUFFER nodes
 pub const BUFFER: usize = 3;
+/// The default minimum age of an Adult
+pub const MIN_ADULT_AGE: u8 = 5;
 
-pub use self::network::{Network, NetworkStructure};
+pub use self::network::{Network, NetworkStructure, Output};
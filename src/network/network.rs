@@ -1,13 +1,22 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt;
 use std::mem;
 use std::iter::{Iterator, Sum};
-use random::{random, shuffle};
-use network::prefix::Prefix;
+use random::{random_seed, Seed, SeededRng};
+use network::prefix::{prefix_map, Name, Prefix};
 use network::node::Node;
-use network::section::Section;
-use network::churn::{NetworkEvent, SectionEvent};
+use network::section::{EventResult, Section};
+use network::attack::{attacker, grind_node, AttackStats, AttackTable, AttackerKind, GrindRows,
+                      GrindStats, PendingAttempt};
+use network::churn::{ExternalEvent, NetworkEvent, SectionEvent};
+use network::elders::{ElderRows, ElderStats, SwapTable};
+use network::relocation::{relocation_policy, RelocationStats};
+use network::failure::{Attribute, Failure, FailureReport, FailureTable};
+use network::invariants::{Violation, RECENT_EVENTS};
+use network::lifecycle::{ChangeKind, ChangeRecord, LifecycleTable};
+use network::trace::{TraceRecord, TracedEvent};
 use params::Params;
+use scenario::{PhaseRecord, PhaseTable};
 use stats::Stats;
 
 /// A wrapper struct that handles merges in progress
@@ -16,8 +25,9 @@ use stats::Stats;
 /// section. This remembers which sections are in the
 /// process of merging and reports whether all of them are
 /// ready to be combined.
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 struct PendingMerge {
+    #[serde(with = "prefix_map")]
     complete: BTreeMap<Prefix, bool>,
 }
 
@@ -48,14 +58,79 @@ impl PendingMerge {
     }
 }
 
-#[derive(Clone, Default)]
+/// The nodes that left the network, in the order the rejoins draw them from, together with an
+/// index of their names. Serialised as the plain list of nodes.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<Node>", into = "Vec<Node>")]
+struct LeftNodes {
+    nodes: Vec<Node>,
+    names: BTreeSet<Name>,
+}
+
+impl LeftNodes {
+    fn push(&mut self, node: Node) {
+        let _ = self.names.insert(node.name());
+        self.nodes.push(node);
+    }
+
+    /// Removes a random node
+    fn pop_random(&mut self, rng: &mut SeededRng) -> Option<Node> {
+        rng.shuffle(&mut self.nodes);
+        let node = self.nodes.pop()?;
+        let _ = self.names.remove(&node.name());
+        Some(node)
+    }
+
+    /// Removes the node with the given name
+    fn remove(&mut self, name: Name) -> Option<Node> {
+        if !self.names.remove(&name) {
+            return None;
+        }
+        let index = self.nodes.iter().position(|n| n.name() == name)?;
+        Some(self.nodes.remove(index))
+    }
+
+    fn contains(&self, name: Name) -> bool {
+        self.names.contains(&name)
+    }
+}
+
+impl From<Vec<Node>> for LeftNodes {
+    fn from(nodes: Vec<Node>) -> Self {
+        let names = nodes.iter().map(|n| n.name()).collect();
+        LeftNodes { nodes, names }
+    }
+}
+
+impl From<LeftNodes> for Vec<Node> {
+    fn from(left: LeftNodes) -> Self {
+        left.nodes
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct NetworkStructure {
+    /// the simulated time, in the continuous-time engine
+    #[serde(default)]
+    pub time: f64,
+    /// the index of the phase of the scenario
+    #[serde(default)]
+    pub phase: usize,
     pub size: usize,
     pub sections: usize,
     pub complete: usize,
+    /// the number of complete sections with at least a third of their elders malicious
+    #[serde(default)]
+    pub third_malicious: usize,
+    /// the number of complete sections with at least half of their elders malicious
+    #[serde(default)]
+    pub half_malicious: usize,
+    /// the number of changes of the sections' elders in the iteration
+    #[serde(default)]
+    pub elder_changes: u64,
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Output {
     /// the number of "add" random events
     pub adds: u64,
@@ -73,39 +148,121 @@ pub struct Output {
     pub churn: u64,
     /// the structure of the network
     pub network_structure: Vec<NetworkStructure>,
+    /// the first iteration in which attackers held at least a third of some section's elders
+    #[serde(default)]
+    pub first_third_malicious: Option<usize>,
+    /// the first iteration in which attackers held at least half of some section's elders, i.e.
+    /// captured it
+    #[serde(default)]
+    pub first_capture: Option<usize>,
+    /// the attempts of the attacker, by target section
+    #[serde(default, with = "prefix_map")]
+    pub attacks: BTreeMap<Prefix, AttackStats>,
+    /// the relocations caused by honest and malicious joins
+    #[serde(default)]
+    pub grinding: GrindStats,
+    /// the mass failures that happened and the recovery from them
+    #[serde(default)]
+    pub failures: Vec<FailureReport>,
+    /// the number of the scheduled failures that happened, as opposed to the ones caused by the
+    /// events of a scenario
+    #[serde(default)]
+    pub scheduled_failures: usize,
+    /// the phases of the scenario entered so far
+    #[serde(default)]
+    pub phases: Vec<PhaseRecord>,
+    /// the relocation distances and the section sizes they lead to
+    #[serde(default)]
+    pub relocation: RelocationStats,
+    /// the changes of the sections' Elders
+    #[serde(default)]
+    pub elders: ElderStats,
+    /// the splits and merges, in the order they were initiated
+    #[serde(default)]
+    pub prefix_changes: Vec<ChangeRecord>,
 }
 
 /// The structure representing the whole network
 /// It's a container for sections that simulates all the
 /// churn and communication between them.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Network {
     /// all the sections in the network indexed by prefixes
+    #[serde(with = "prefix_map")]
     nodes: BTreeMap<Prefix, Section>,
     /// the nodes that left the network and could rejoin in the future
-    left_nodes: Vec<Node>,
+    left_nodes: LeftNodes,
     /// queues of events to be processed by each section
+    #[serde(with = "prefix_map")]
     event_queue: BTreeMap<Prefix, Vec<NetworkEvent>>,
     /// prefixes that are in the process of merging
+    #[serde(with = "prefix_map")]
     pending_merges: BTreeMap<Prefix, PendingMerge>,
     /// Simulation parameters
     params: Params,
     /// Simulation outputs
     output: Output,
+    /// the number of processed iterations
+    iteration: usize,
+    /// the random number generator of this simulation
+    rng: SeededRng,
+    /// the records of the event trace, if tracing is enabled
+    #[serde(skip)]
+    trace: Option<Vec<TraceRecord>>,
+    /// names to be used in the next relocations instead of random ones
+    #[serde(skip)]
+    relocation_names: VecDeque<Name>,
+    /// sections to be the targets of the next relocations instead of the ones chosen by the
+    /// relocation policy
+    #[serde(skip)]
+    relocation_targets: VecDeque<Prefix>,
+    /// the most recent trace records, kept for reporting violated invariants
+    #[serde(skip)]
+    recent_events: VecDeque<TraceRecord>,
+    /// the attempts of the attacker whose outcome isn't known yet
+    #[serde(default)]
+    pending_attempts: Vec<PendingAttempt>,
+    /// the names of the nodes that have just joined, whose `Live` events haven't been processed
+    #[serde(default)]
+    joining: BTreeSet<Name>,
+    /// the old and new names of the relocated nodes, if they are being tracked
+    #[serde(default)]
+    renames: Option<Vec<(Name, Name)>>,
+    /// the simulated time, in the continuous-time engine
+    #[serde(default)]
+    time: f64,
+    /// the true reliability of every node in the sections or among the left nodes, if the nodes
+    /// are dropped by it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    reliabilities: BTreeMap<Name, f64>,
 }
 
 impl Network {
-    /// Starts a new network
-    pub fn new(params: Params) -> Network {
+    /// Starts a new network. If the parameters don't specify a seed, a random one is chosen and
+    /// recorded in the parameters.
+    pub fn new(mut params: Params) -> Network {
+        let seed = params.seed.unwrap_or_else(random_seed);
+        params.seed = Some(seed);
         let mut nodes = BTreeMap::new();
         nodes.insert(Prefix::empty(), Section::new(Prefix::empty()));
         Network {
             nodes,
-            left_nodes: Vec::new(),
+            left_nodes: LeftNodes::default(),
             event_queue: BTreeMap::new(),
             pending_merges: BTreeMap::new(),
             params,
             output: Default::default(),
+            iteration: 0,
+            rng: SeededRng::new(seed),
+            trace: None,
+            relocation_names: VecDeque::new(),
+            relocation_targets: VecDeque::new(),
+            recent_events: VecDeque::new(),
+            pending_attempts: Vec::new(),
+            joining: BTreeSet::new(),
+            renames: None,
+            time: 0.0,
+            reliabilities: BTreeMap::new(),
         }
     }
 
@@ -115,37 +272,111 @@ impl Network {
     }
 
     fn capture_network_structure(&mut self) {
+        let (third_malicious, half_malicious) = self.malicious_sections();
+        let sizes: Vec<_> = self.nodes.values().map(|s| s.len()).collect();
+        self.output.relocation.record_sizes(&sizes);
         let structure = NetworkStructure {
+            time: self.time,
+            phase: self.output.phases.len().saturating_sub(1),
             size: self.nodes.values().map(|x| x.len()).sum(),
             sections: self.nodes.len(),
-            complete: self.nodes.values().filter(|x| x.is_complete()).count(),
+            complete: self.complete_sections(),
+            third_malicious,
+            half_malicious,
+            elder_changes: self.output.elders.end_iteration(),
         };
+        if third_malicious > 0 && self.output.first_third_malicious.is_none() {
+            self.output.first_third_malicious = Some(self.iteration);
+        }
+        if half_malicious > 0 && self.output.first_capture.is_none() {
+            self.output.first_capture = Some(self.iteration);
+        }
         self.output.network_structure.push(structure);
     }
 
+    /// Returns the numbers of complete sections with at least a third and at least half of their
+    /// elders malicious. Incomplete sections are skipped, as a single malicious node would already
+    /// hold a large part of the few elders of a starting network.
+    fn malicious_sections(&self) -> (usize, usize) {
+        let mut result = (0, 0);
+        for section in self.nodes.values() {
+            let malicious = section.malicious_elders();
+            if malicious == 0 || !section.is_complete(&self.params) {
+                continue;
+            }
+            let elders = section.elders().len();
+            if 3 * malicious >= elders {
+                result.0 += 1;
+            }
+            if 2 * malicious >= elders {
+                result.1 += 1;
+            }
+        }
+        result
+    }
+
     /// Sends all events to the corresponding sections and processes the events passed
     /// back. The responses generate new events and the cycle continues until the queues are empty.
     /// Then. if any pending merges are ready, they are processed, too.
     pub fn process_events(&mut self) {
+        let old_elders: BTreeMap<_, _> = self.nodes
+            .iter()
+            .map(|(pfx, section)| (*pfx, section.elder_names().clone()))
+            .collect();
         while self.has_events() {
-            let queue = mem::replace(&mut self.event_queue, BTreeMap::new());
+            let queue = mem::take(&mut self.event_queue);
             for (prefix, events) in queue {
                 let mut section_events = vec![];
                 for event in events {
-                    let params = &self.params;
-                    let result = self.nodes
-                        .get_mut(&prefix)
-                        .map(|section| section.handle_event(event, params))
-                        .unwrap_or_else(Vec::new);
-                    section_events.extend(result);
+                    if let NetworkEvent::Live(node, _) = event {
+                        self.check_live_event(prefix, node);
+                    }
+                    // the elders handed over in a merge are taken from a snapshot of the merging
+                    // sections, so one of them could have been lost in the same round of events;
+                    // handing it over would bring a node that left back to life
+                    let (result, events) = match event {
+                        NetworkEvent::Live(node, false) if self.has_left(node.name()) => {
+                            (EventResult::Ignored, vec![])
+                        }
+                        _ => {
+                            let params = &self.params;
+                            let rng = &mut self.rng;
+                            self.nodes
+                                .get_mut(&prefix)
+                                .map(|section| section.handle_event(event, params, rng))
+                                .unwrap_or((EventResult::Ignored, vec![]))
+                        }
+                    };
+                    self.record(prefix, TracedEvent::Network(event), result);
+                    if let NetworkEvent::Live(node, true) = event {
+                        if self.joining.remove(&node.name()) {
+                            let relocated = events.iter().find_map(|event| match *event {
+                                SectionEvent::NeedRelocate(relocated) => Some(relocated),
+                                _ => None,
+                            });
+                            self.output.grinding.record_join(node, relocated);
+                        }
+                    }
+                    if let NetworkEvent::Lost(name) = event {
+                        // an infant leaving a complete section isn't kept among the left nodes,
+                        // so it can never rejoin
+                        let dropped = events
+                            .iter()
+                            .any(|event| matches!(*event, SectionEvent::NodeDropped(_)));
+                        if !dropped {
+                            let _ = self.reliabilities.remove(&name);
+                        }
+                    }
+                    section_events.extend(events.into_iter().map(|ev| (event, ev)));
                     if let NetworkEvent::PrefixChange(pfx) = event {
                         if let Some(pending_merge) = self.pending_merges.get_mut(&pfx) {
                             pending_merge.completed(prefix);
                         }
                     }
                 }
-                for section_event in section_events {
-                    self.process_single_event(prefix, section_event);
+                for (cause, section_event) in section_events {
+                    let result = self.process_single_event(prefix, section_event, cause);
+                    self.record(prefix, TracedEvent::Section(section_event), result);
                 }
             }
         }
@@ -155,49 +386,206 @@ impl Network {
             .map(|(pfx, _)| *pfx)
             .collect();
         for pfx in merges_to_finalise {
-            println!("Finalising a merge into {:?}", pfx);
+            log!(self.params, "Finalising a merge into {:?}", pfx);
             self.output.churn += 1; // counting merge as a single churn event
             let pending_merge = self.pending_merges.remove(&pfx).unwrap().into_map();
             let merged_section = self.merged_section(pending_merge.keys(), true);
+            let iteration = self.iteration;
+            if let Some(record) = self.pending_change(ChangeKind::Merge, pfx) {
+                record.finalised = Some(iteration);
+            }
             self.nodes.insert(merged_section.prefix(), merged_section);
+            for report in self.output.failures.iter_mut().filter(|r| r.is_recovering()) {
+                report.merges += 1;
+            }
         }
+        self.record_elder_changes(&old_elders);
+        self.update_recoveries();
+        if self.params.check_invariants {
+            if let Err(violation) = self.check_invariants() {
+                self.invariant_violated(violation);
+            }
+        }
+        self.joining.clear();
         self.capture_network_structure();
+        self.iteration += 1;
+    }
+
+    /// Records the changes of the sections' Elders in the iteration, given the Elders the
+    /// sections had before it. Every section is compared once, however many events it handled,
+    /// against the Elders of the sections it split from or merged from, so that a split costs
+    /// a DKG round for each half and a merge a single one.
+    fn record_elder_changes(&mut self, old_elders: &BTreeMap<Prefix, BTreeSet<Name>>) {
+        for (prefix, section) in &self.nodes {
+            let old: BTreeSet<_> = old_elders
+                .iter()
+                .filter(|&(pfx, _)| pfx.is_compatible_with(prefix))
+                .flat_map(|(_, elders)| elders.iter().cloned())
+                .collect();
+            self.output.elders.record(*prefix, &old, section.elder_names());
+        }
+    }
+
+    /// Marks the failures after which the number of complete sections got back to what it was
+    /// before as recovered
+    fn update_recoveries(&mut self) {
+        let complete = self.complete_sections();
+        let (iteration, time) = (self.iteration, self.time);
+        for report in self.output.failures.iter_mut().filter(|r| r.is_recovering()) {
+            if complete >= report.complete_before {
+                report.recovery_iterations = Some(iteration - report.iteration);
+                report.recovery_time = Some(time - report.time);
+            }
+        }
+    }
+
+    /// Adds a record to the event trace, if tracing is enabled
+    fn record(&mut self, prefix: Prefix, event: TracedEvent, result: EventResult) {
+        let record = TraceRecord {
+            iteration: self.iteration,
+            prefix,
+            event,
+            result,
+        };
+        if let Some(ref mut trace) = self.trace {
+            trace.push(record);
+        }
+        if self.params.check_invariants {
+            if self.recent_events.len() == RECENT_EVENTS {
+                let _ = self.recent_events.pop_front();
+            }
+            self.recent_events.push_back(record);
+        }
+    }
+
+    /// Makes sure that a node is only passed to a section it can belong to, if the invariants are
+    /// being checked
+    fn check_live_event(&self, prefix: Prefix, node: Node) {
+        if !self.params.check_invariants {
+            return;
+        }
+        if let Some(section) = self.nodes.get(&prefix) {
+            if !section.accepts(node.name()) {
+                self.invariant_violated(Violation::ForeignNode(prefix, node));
+            }
+        }
     }
 
-    /// Processes a single response from a section and potentially inserts some events into its
-    /// queue
-    fn process_single_event(&mut self, prefix: Prefix, event: SectionEvent) {
+    /// Checks the consistency of the network: the sections must cover the whole name space
+    /// without overlapping, their nodes must be consistent, and no node can be in two sections at
+    /// once (other than while they are merging) or both in a section and among the left nodes
+    pub fn check_invariants(&self) -> Result<(), Violation> {
+        let mut coverage = 0u128;
+        for prefix in self.nodes.keys() {
+            let mut ancestor = *prefix;
+            while !ancestor.is_empty() {
+                ancestor = ancestor.shorten();
+                if self.nodes.contains_key(&ancestor) {
+                    return Err(Violation::Overlap(ancestor, *prefix));
+                }
+            }
+            coverage += 1u128 << (64 - prefix.len());
+        }
+        if coverage != 1u128 << 64 {
+            return Err(Violation::Coverage);
+        }
+        let mut locations = BTreeMap::new();
+        for (prefix, section) in &self.nodes {
+            section.check_invariants(&self.params)?;
+            for node in section.nodes() {
+                if let Some(other) = locations.insert(node.name(), *prefix) {
+                    let merging = self.pending_merges
+                        .keys()
+                        .any(|merged| merged.is_ancestor(prefix) && merged.is_ancestor(&other));
+                    if !merging {
+                        return Err(Violation::Duplicate(node.name(), other, *prefix));
+                    }
+                }
+            }
+        }
+        for node in &self.left_nodes.nodes {
+            if let Some(prefix) = locations.get(&node.name()) {
+                return Err(Violation::LeftAndLive(node.name(), *prefix));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports a violated invariant together with the offending sections and the most recent
+    /// events, and stops the simulation
+    fn invariant_violated(&self, violation: Violation) -> ! {
+        let mut report = format!(
+            "Invariant violated in iteration {}: {}\n",
+            self.iteration, violation
+        );
+        let mut prefixes: Vec<_> = violation.prefix().into_iter().collect();
+        match violation {
+            Violation::Overlap(_, other) | Violation::Duplicate(_, _, other) => prefixes.push(other),
+            _ => (),
+        }
+        for section in prefixes.iter().filter_map(|prefix| self.nodes.get(prefix)) {
+            report.push_str(&section.dump());
+        }
+        if let Violation::Coverage = violation {
+            report.push_str(&format!("Sections: {:?}\n", self.nodes.keys().collect::<Vec<_>>()));
+        }
+        report.push_str(&format!("Last {} events:\n", self.recent_events.len()));
+        for record in &self.recent_events {
+            report.push_str(&format!("\t{:?}\n", record));
+        }
+        panic!("{}", report);
+    }
+
+    /// Processes a single response from a section to the network event `cause` and potentially
+    /// inserts some events into its queue. Returns whether the response was handled or ignored.
+    fn process_single_event(
+        &mut self,
+        prefix: Prefix,
+        event: SectionEvent,
+        cause: NetworkEvent,
+    ) -> EventResult {
         match event {
             SectionEvent::NodeDropped(node) => {
                 self.left_nodes.push(node);
             }
             SectionEvent::NeedRelocate(node) => {
-                self.relocate(node);
+                self.relocate(node, cause);
             }
-            SectionEvent::NodeRejected(_) => {
+            SectionEvent::NodeRejected(node) => {
                 self.output.rejections += 1;
+                // a rejected node is gone for good, unlike the dropped ones that can rejoin
+                let _ = self.reliabilities.remove(&node.name());
             }
             SectionEvent::RequestMerge => {
-                self.merge(prefix);
+                return self.merge(prefix);
             }
             SectionEvent::RequestSplit => {
-                if let Some(section) = self.nodes.remove(&prefix) {
+                let mut record = ChangeRecord::new(ChangeKind::Split, prefix, self.iteration, 1);
+                let section = self.nodes.remove(&prefix);
+                // the section could have merged since it requested the split
+                record.superseded = section.is_none();
+                record.finalised = section.as_ref().map(|_| self.iteration);
+                self.output.prefix_changes.push(record);
+                if let Some(section) = section {
                     let ((sec0, ev0), (sec1, ev1)) = section.split(&self.params);
                     let _ = self.event_queue.remove(&prefix);
                     self.event_queue
                         .entry(sec0.prefix())
-                        .or_insert_with(Vec::new)
+                        .or_default()
                         .extend(ev0);
                     self.event_queue
                         .entry(sec1.prefix())
-                        .or_insert_with(Vec::new)
+                        .or_default()
                         .extend(ev1);
                     self.nodes.insert(sec0.prefix(), sec0);
                     self.nodes.insert(sec1.prefix(), sec1);
                     self.output.churn += 1; // counting the split as one churn event
+                } else {
+                    return EventResult::Ignored;
                 }
             }
         }
+        EventResult::Handled
     }
 
     /// Returns the section that would be the result of merging sections with the given prefixes.
@@ -234,23 +622,36 @@ impl Network {
 
     /// Calculates which sections will merge into a given prefix, creates a pending merge for them
     /// and prepares queues for churn events to be processed before the merge itself.
-    fn merge(&mut self, prefix: Prefix) {
+    /// Returns `Ignored` if the prefix is already part of a larger merge.
+    fn merge(&mut self, prefix: Prefix) -> EventResult {
         let merged_pfx = prefix.shorten();
+        let prefixes: Vec<_> = self.nodes
+            .keys()
+            .filter(|&pfx| merged_pfx.is_ancestor(pfx))
+            .cloned()
+            .collect();
+        let record = ChangeRecord::new(ChangeKind::Merge, merged_pfx, self.iteration, prefixes.len());
         if let Some(&compatible_merge) = self.pending_merges
             .keys()
             .find(|pfx| pfx.is_compatible_with(&merged_pfx))
         {
             if compatible_merge.is_ancestor(&merged_pfx) {
-                return;
+                if compatible_merge != merged_pfx {
+                    // a larger merge already under way covers this one
+                    self.output.prefix_changes.push(ChangeRecord {
+                        superseded: true,
+                        ..record
+                    });
+                }
+                return EventResult::Ignored;
             }
             let _ = self.pending_merges.remove(&compatible_merge);
+            if let Some(replaced) = self.pending_change(ChangeKind::Merge, compatible_merge) {
+                replaced.superseded = true;
+            }
         }
-        println!("Initiating a merge into {:?}", merged_pfx);
-        let prefixes: Vec<_> = self.nodes
-            .keys()
-            .filter(|&pfx| merged_pfx.is_ancestor(pfx))
-            .cloned()
-            .collect();
+        log!(self.params, "Initiating a merge into {:?}", merged_pfx);
+        self.output.prefix_changes.push(record);
 
         let pending_merge = PendingMerge::from_prefixes(prefixes.iter().cloned());
         self.pending_merges.insert(merged_pfx, pending_merge);
@@ -260,6 +661,16 @@ impl Network {
             let events = self.calculate_merge_events(&merged_section, pfx);
             let _ = self.event_queue.insert(pfx, events);
         }
+        EventResult::Handled
+    }
+
+    /// Returns the record of the split or merge of the given prefix that is still under way
+    fn pending_change(&mut self, kind: ChangeKind, prefix: Prefix) -> Option<&mut ChangeRecord> {
+        self.output
+            .prefix_changes
+            .iter_mut()
+            .rev()
+            .find(|r| r.kind == kind && r.prefix == prefix && r.is_pending())
     }
 
     /// Creates the queue of events to be processed by a section `pfx` when it merges into
@@ -278,16 +689,45 @@ impl Network {
         events
     }
 
-    /// Adds a random node to the network by pushing an appropriate event to the queue
-    pub fn add_random_node(&mut self) {
+    /// Applies a churn event coming from the outside of the network
+    pub fn apply(&mut self, event: ExternalEvent) {
+        match event {
+            ExternalEvent::Add(node) => self.add_node(node),
+            ExternalEvent::Drop(name) => self.drop_node(name),
+            ExternalEvent::Rejoin(name) => self.rejoin_node(name),
+        }
+    }
+
+    /// Adds a random node to the network by pushing an appropriate event to the queue and
+    /// returns the node
+    pub fn add_random_node(&mut self) -> Node {
+        let fraction = self.params.malicious_fraction;
+        // only draw from the RNG when needed, so that runs without attackers stay the same
+        let node = if fraction > 0.0 && self.rng.random::<f64>() < fraction {
+            let init_age = self.params.init_age;
+            grind_node(self, init_age)
+        } else {
+            Node::new(self.rng.random(), self.params.init_age)
+        };
+        self.add_node(node);
+        node
+    }
+
+    /// Adds the given node to the network by pushing an appropriate event to the queue
+    pub fn add_node(&mut self, node: Node) {
         self.output.adds += 1;
         self.output.churn += 1;
-        let node = Node::new(random(), self.params.init_age);
-        println!("Adding node {:?}", node);
+        log!(self.params, "Adding node {:?}", node);
+        if self.params.true_reliability {
+            let reliability = self.params.session.sample(&mut self.rng);
+            let _ = self.reliabilities.insert(node.name(), reliability);
+        }
         let prefix = self.prefix_for_node(node).unwrap();
+        self.record(prefix, TracedEvent::External(ExternalEvent::Add(node)), EventResult::Handled);
+        let _ = self.joining.insert(node.name());
         self.event_queue
             .entry(prefix)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(NetworkEvent::Live(node, true));
     }
 
@@ -296,12 +736,36 @@ impl Network {
     /// have less chance of dropping. This helps in calculating which node should be dropped.
     fn total_drop_weight(&self) -> f64 {
         self.nodes
-            .iter()
-            .flat_map(|(_, s)| s.nodes().into_iter())
-            .map(|n| n.drop_probability(self.params.drop_dist))
+            .values()
+            .flat_map(|s| s.nodes().into_iter())
+            .map(|n| self.drop_weight(&n))
             .sum()
     }
 
+    /// Returns the median true reliability of the nodes in the sections, or of the elders only,
+    /// which shows how well ageing picks the reliable nodes as elders
+    pub fn median_reliability(&self, elders_only: bool) -> Option<f64> {
+        let mut reliabilities: Vec<f64> = self.nodes
+            .values()
+            .flat_map(|s| if elders_only { s.elders() } else { s.nodes() })
+            .filter_map(|n| self.reliabilities.get(&n.name()).cloned())
+            .collect();
+        if reliabilities.is_empty() {
+            return None;
+        }
+        reliabilities.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        Some(reliabilities[reliabilities.len() / 2])
+    }
+
+    /// Returns the weight of the node in choosing the one to be dropped: inversely proportional
+    /// to its true reliability if the nodes are dropped by it, and depending on its age otherwise
+    fn drop_weight(&self, node: &Node) -> f64 {
+        match self.reliabilities.get(&node.name()) {
+            Some(reliability) if self.params.true_reliability => 1.0 / reliability,
+            _ => node.drop_probability(&self.params.drop_dist),
+        }
+    }
+
     /// Returns the prefix a node should belong to.
     fn prefix_for_node(&self, node: Node) -> Option<Prefix> {
         self.nodes
@@ -310,71 +774,188 @@ impl Network {
             .cloned()
     }
 
-    /// Chooses a new section for the given node, generates a new name for it,
-    /// increases its age,  and sends a `Live` event to the section.
-    fn relocate(&mut self, mut node: Node) {
+    /// Chooses a new section for the given node according to the relocation policy, generates a
+    /// new name for it, increases its age,  and sends a `Live` event to the section. `cause` is
+    /// the churn event that triggered the relocation.
+    fn relocate(&mut self, mut node: Node, cause: NetworkEvent) {
         self.output.relocations += 1;
         self.output.churn += 2; // leaving one section and joining another one
-        let (node, neighbour) = {
-            let src_section = self.nodes
-                .keys()
-                .find(|&pfx| pfx.matches(node.name()))
-                .unwrap();
-            let mut neighbours: Vec<_> = self.nodes
-                .keys()
-                .filter(|&pfx| pfx.is_neighbour(src_section))
-                .collect();
-            // relocate to the neighbour with the least peers as per the document
-            neighbours.sort_by_key(|pfx| pfx.len() as usize * 10000 + self.nodes.get(pfx).unwrap().len());
-            let neighbour = if let Some(n) = neighbours.first() {
-                n
-            } else {
-                src_section
-            };
-            let old_node = node.clone();
-            node.relocate(neighbour);
-            println!(
-                "Relocating {:?} from {:?} to {:?} as {:?}",
-                old_node, src_section, neighbour, node
-            );
-            (node, neighbour)
+        let random_name = self.relocation_names
+            .pop_front()
+            .unwrap_or_else(|| Name(self.rng.random()));
+        let src_section = self.prefix_for_node(node).unwrap();
+        let neighbour = match self.relocation_targets.pop_front() {
+            Some(target) => target,
+            None => {
+                relocation_policy(self.params.relocation).target(self, src_section, &node, cause)
+            }
         };
+        let old_node = node;
+        node.relocate(&neighbour, random_name);
+        log!(
+            self.params,
+            "Relocating {:?} from {:?} to {:?} as {:?}",
+            old_node, src_section, neighbour, node
+        );
+        self.output.relocation.record_relocation(src_section, neighbour);
+        self.record(src_section, TracedEvent::Relocation(old_node, node, neighbour), EventResult::Handled);
+        if let Some(ref mut renames) = self.renames {
+            renames.push((old_node.name(), node.name()));
+        }
+        if let Some(reliability) = self.reliabilities.remove(&old_node.name()) {
+            let _ = self.reliabilities.insert(node.name(), reliability);
+        }
         self.event_queue
-            .entry(*neighbour)
-            .or_insert_with(Vec::new)
+            .entry(neighbour)
+            .or_default()
             .push(NetworkEvent::Live(node, true));
     }
 
+    /// Makes the next relocations use the given names (with the target prefix substituted in)
+    /// instead of random ones, in order. Used to reproduce recorded relocations exactly.
+    pub fn push_relocation_names<I: IntoIterator<Item = Name>>(&mut self, names: I) {
+        self.relocation_names.extend(names);
+    }
+
+    /// Makes the next relocations move the nodes to the given sections instead of the ones
+    /// chosen by the relocation policy, in order. Used to reproduce recorded relocations exactly,
+    /// even with policies drawing from the random number generator.
+    pub fn push_relocation_targets<I: IntoIterator<Item = Prefix>>(&mut self, targets: I) {
+        self.relocation_targets.extend(targets);
+    }
+
     /// Drops a random node from the network by sending a `Lost` event to the section.
     /// The probability of a given node dropping is weighted based on its age.
     pub fn drop_random_node(&mut self) {
-        self.output.drops += 1;
-        self.output.churn += 1;
         let total_weight = self.total_drop_weight();
-        let mut drop = random::<f64>() * total_weight;
+        let mut drop = self.rng.random::<f64>() * total_weight;
         let node_and_prefix = {
             let mut res = None;
             let nodes_iter = self.nodes
                 .iter()
                 .flat_map(|(p, s)| s.nodes().into_iter().map(move |n| (*p, n)));
             for (p, n) in nodes_iter {
-                if n.drop_probability(self.params.drop_dist) > drop {
+                let weight = self.drop_weight(&n);
+                if weight > drop {
                     res = Some((p, n));
                     break;
                 }
-                drop -= n.drop_probability(self.params.drop_dist);
+                drop -= weight;
             }
             res
         };
-        node_and_prefix.map(|(prefix, node)| {
-            *self.output.drops_dist.entry(node.age()).or_insert(0) += 1;
-            let name = node.name();
-            println!("Dropping node {:?} from section {:?}", name, prefix);
-            self.event_queue
-                .entry(prefix)
-                .or_insert_with(Vec::new)
-                .push(NetworkEvent::Lost(name));
+        // no node may be dropped, e.g. if the drop distribution gives every node a zero weight
+        let (prefix, node) = match node_and_prefix {
+            Some(node_and_prefix) => node_and_prefix,
+            None => return,
+        };
+        if node.is_malicious() && !attacker(self.params.attacker).agrees_to_leave(self, prefix, node) {
+            log!(self.params, "Node {:?} refuses to leave {:?}", node, prefix);
+            return;
+        }
+        // a refused drop doesn't happen, so it isn't counted
+        self.output.drops += 1;
+        self.output.churn += 1;
+        self.lose_node(prefix, node);
+    }
+
+    /// Drops the node with the given name from the network by sending a `Lost` event to the
+    /// section.
+    pub fn drop_node(&mut self, name: Name) {
+        self.output.drops += 1;
+        self.output.churn += 1;
+        let node_and_prefix = self.nodes
+            .iter()
+            .filter(|&(p, _)| p.matches(name))
+            .flat_map(|(p, s)| s.nodes().into_iter().map(move |n| (*p, n)))
+            .find(|&(_, n)| n.name() == name);
+        if let Some((prefix, node)) = node_and_prefix {
+            self.lose_node(prefix, node);
+        }
+    }
+
+    /// Drops all the nodes hit by the failure at once, by sending `Lost` events to their
+    /// sections, and starts tracking the recovery of the network
+    pub fn mass_failure(&mut self, failure: Failure) {
+        let mut hit: Vec<(Prefix, Node)> = match failure {
+            Failure::Percent(_) => self.nodes
+                .iter()
+                .flat_map(|(p, s)| s.nodes().into_iter().map(move |n| (*p, n)))
+                .collect(),
+            Failure::Prefix(ref prefix) => self.nodes
+                .iter()
+                .flat_map(|(p, s)| s.nodes().into_iter().map(move |n| (*p, n)))
+                .filter(|&(_, n)| prefix.matches(n.name()))
+                .collect(),
+            Failure::Attribute(attribute) => {
+                let min_adult_age = self.params.min_adult_age;
+                self.nodes
+                    .iter()
+                    .flat_map(|(p, s)| {
+                        let elders = s.elders();
+                        s.nodes()
+                            .into_iter()
+                            .filter(move |n| match attribute {
+                                Attribute::Malicious => n.is_malicious(),
+                                Attribute::Honest => !n.is_malicious(),
+                                Attribute::Elders => elders.contains(n),
+                                Attribute::Adults => n.is_adult(min_adult_age),
+                                Attribute::Infants => !n.is_adult(min_adult_age),
+                                Attribute::Age(age) => n.age() == age,
+                            })
+                            .map(move |n| (*p, n))
+                    })
+                    .collect()
+            }
+        };
+        if let Failure::Percent(percent) = failure {
+            let count = (hit.len() as f64 * percent / 100.0).round() as usize;
+            self.rng.shuffle(&mut hit);
+            hit.truncate(count);
+            hit.sort();
+        }
+        let hit_names: BTreeSet<_> = hit.iter().map(|&(_, n)| n.name()).collect();
+        let lost_complete = self.nodes
+            .values()
+            .filter(|s| s.is_complete(&self.params))
+            .filter(|s| {
+                let adults_left = s.nodes()
+                    .iter()
+                    .filter(|n| n.is_adult(self.params.min_adult_age))
+                    .filter(|n| !hit_names.contains(&n.name()))
+                    .count();
+                adults_left < self.params.group_size
+            })
+            .count();
+        log!(self.params, "Mass failure {} hits {} nodes", failure, hit.len());
+        self.output.failures.push(FailureReport {
+            failure: failure.to_string(),
+            iteration: self.iteration,
+            time: self.time,
+            dropped: hit.len(),
+            complete_before: self.complete_sections(),
+            lost_complete,
+            merges: 0,
+            recovery_iterations: None,
+            recovery_time: None,
         });
+        for (prefix, node) in hit {
+            self.output.drops += 1;
+            self.output.churn += 1;
+            self.lose_node(prefix, node);
+        }
+    }
+
+    /// Sends a `Lost` event for the node to its section
+    fn lose_node(&mut self, prefix: Prefix, node: Node) {
+        *self.output.drops_dist.entry(node.age()).or_insert(0) += 1;
+        let name = node.name();
+        log!(self.params, "Dropping node {:?} from section {:?}", name, prefix);
+        self.record(prefix, TracedEvent::External(ExternalEvent::Drop(name)), EventResult::Handled);
+        self.event_queue
+            .entry(prefix)
+            .or_default()
+            .push(NetworkEvent::Lost(name));
     }
 
     /// Chooses a random node from among the ones that left the network and gets it to rejoin.
@@ -382,25 +963,41 @@ impl Network {
     pub fn rejoin_random_node(&mut self) {
         self.output.rejoins += 1;
         self.output.churn += 1;
-        shuffle(&mut self.left_nodes);
-        if let Some(mut node) = self.left_nodes.pop() {
-            println!("Rejoining node {:?}", node);
-            node.rejoined(self.params.init_age);
-            let prefix = self.prefix_for_node(node).unwrap();
-            self.event_queue
-                .entry(prefix)
-                .or_insert_with(Vec::new)
-                .push(NetworkEvent::Live(node, true));
+        if let Some(node) = self.left_nodes.pop_random(&mut self.rng) {
+            self.rejoin(node);
         }
     }
 
+    /// Gets the node with the given name, which left the network before, to rejoin.
+    /// The age of the rejoining node is reduced.
+    pub fn rejoin_node(&mut self, name: Name) {
+        self.output.rejoins += 1;
+        self.output.churn += 1;
+        if let Some(node) = self.left_nodes.remove(name) {
+            self.rejoin(node);
+        }
+    }
+
+    /// Sends a `Live` event for a node that left the network before to its section
+    fn rejoin(&mut self, mut node: Node) {
+        log!(self.params, "Rejoining node {:?}", node);
+        let name = node.name();
+        node.rejoined(self.params.init_age);
+        let prefix = self.prefix_for_node(node).unwrap();
+        self.record(prefix, TracedEvent::External(ExternalEvent::Rejoin(name)), EventResult::Handled);
+        self.event_queue
+            .entry(prefix)
+            .or_default()
+            .push(NetworkEvent::Live(node, true));
+    }
+
     pub fn num_sections(&self) -> usize {
         self.nodes.len()
     }
 
     pub fn age_distribution(&self) -> BTreeMap<u8, usize> {
         let mut result = BTreeMap::new();
-        for (_, section) in &self.nodes {
+        for section in self.nodes.values() {
             for node in section.nodes() {
                 *result.entry(node.age()).or_insert(0) += 1;
             }
@@ -409,12 +1006,118 @@ impl Network {
     }
 
     pub fn complete_sections(&self) -> usize {
-        self.nodes.iter().filter(|&(_, s)| s.is_complete()).count()
+        self.nodes
+            .values()
+            .filter(|s| s.is_complete(&self.params))
+            .count()
     }
 
     pub fn output(&self) -> &Output {
         &self.output
     }
+
+    pub fn output_mut(&mut self) -> &mut Output {
+        &mut self.output
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut Params {
+        &mut self.params
+    }
+
+    /// Returns the seed of the random number generator
+    pub fn seed(&self) -> Seed {
+        self.rng.seed()
+    }
+
+    /// Returns the random number generator of this simulation
+    pub fn rng_mut(&mut self) -> &mut SeededRng {
+        &mut self.rng
+    }
+
+    /// Enables or disables recording of the event trace
+    pub fn set_tracing(&mut self, enabled: bool) {
+        if !enabled {
+            self.trace = None;
+        } else if self.trace.is_none() {
+            self.trace = Some(vec![]);
+        }
+    }
+
+    /// Returns the trace records collected since the last call, leaving the trace empty
+    pub fn take_trace(&mut self) -> Vec<TraceRecord> {
+        self.trace.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    /// Returns the number of times the event queues have been processed
+    pub fn iteration(&self) -> usize {
+        self.iteration
+    }
+
+    /// Returns all the sections in the network indexed by prefixes
+    pub fn sections(&self) -> &BTreeMap<Prefix, Section> {
+        &self.nodes
+    }
+
+    /// Returns the nodes that left the network and could rejoin in the future
+    pub fn left_nodes(&self) -> &[Node] {
+        &self.left_nodes.nodes
+    }
+
+    /// Returns whether a node with the given name is in one of the sections
+    pub fn has_node(&self, name: Name) -> bool {
+        self.nodes
+            .iter()
+            .filter(|&(prefix, _)| prefix.matches(name))
+            .any(|(_, section)| section.contains(name))
+    }
+
+    /// Returns whether a node with the given name is among the nodes that left the network
+    pub fn has_left(&self, name: Name) -> bool {
+        self.left_nodes.contains(name)
+    }
+
+    /// Enables or disables recording of the old and new names of relocated nodes
+    pub fn set_tracking_renames(&mut self, enabled: bool) {
+        if !enabled {
+            self.renames = None;
+        } else if self.renames.is_none() {
+            self.renames = Some(vec![]);
+        }
+    }
+
+    /// Returns the old and new names of the nodes relocated since the last call
+    pub fn take_renames(&mut self) -> Vec<(Name, Name)> {
+        self.renames.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    /// Returns the simulated time
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Sets the simulated time, recorded with the network structure
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Returns the attacker's statistics for the given target section for modification
+    pub fn attack_stats_mut(&mut self, target: Prefix) -> &mut AttackStats {
+        self.output.attacks.entry(target).or_default()
+    }
+
+    /// Remembers an attempt of the attacker to be settled later
+    pub fn push_pending_attempt(&mut self, attempt: PendingAttempt) {
+        self.pending_attempts.push(attempt);
+    }
+
+    /// Returns the attempts of the attacker that haven't been settled yet, leaving none
+    pub fn take_pending_attempts(&mut self) -> Vec<PendingAttempt> {
+        mem::take(&mut self.pending_attempts)
+    }
 }
 
 impl fmt::Debug for Network {
@@ -430,7 +1133,7 @@ impl fmt::Debug for Network {
             self.output.churn,
             usize::sum(self.nodes.values().map(|s| s.len())),
             self.nodes.values(),
-            self.left_nodes
+            self.left_nodes.nodes
         )
     }
 }
@@ -439,36 +1142,97 @@ impl fmt::Debug for Network {
 impl fmt::Display for Network {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         // Network summary
-        try!(writeln!(fmt, "|    Metrics    |  Values  |"));
-        try!(writeln!(fmt, "|:--------------|---------:|"));
-        try!(writeln!(fmt, "| Adds          | {} |", self.output.adds));
-        try!(writeln!(fmt, "| Drops         | {} |", self.output.drops));
-        try!(writeln!(fmt, "| Rejoins       | {} |", self.output.rejoins));
-        try!(writeln!(fmt, "| Relocations   | {} |", self.output.relocations));
-        try!(writeln!(fmt, "| Rejections    | {} |", self.output.rejections));
-        try!(writeln!(fmt, "| Churns        | {} |", self.output.churn));
+        writeln!(fmt, "|    Metrics    |  Values  |")?;
+        writeln!(fmt, "|:--------------|---------:|")?;
+        writeln!(fmt, "| Adds          | {} |", self.output.adds)?;
+        writeln!(fmt, "| Drops         | {} |", self.output.drops)?;
+        writeln!(fmt, "| Rejoins       | {} |", self.output.rejoins)?;
+        writeln!(fmt, "| Relocations   | {} |", self.output.relocations)?;
+        writeln!(fmt, "| Rejections    | {} |", self.output.rejections)?;
+        writeln!(fmt, "| Churns        | {} |", self.output.churn)?;
+        if self.params.is_timed() {
+            writeln!(fmt, "| Time          | {:.2} |", self.time)?;
+        }
         let sections = self.num_sections();
-        try!(writeln!(fmt, "| Sections      | {} |", sections));
+        writeln!(fmt, "| Sections      | {} |", sections)?;
         let complete = self.complete_sections();
         if complete != sections {
-            try!(writeln!(fmt, "| Complete      | {} |", complete));
+            writeln!(fmt, "| Complete      | {} |", complete)?;
+        }
+        writeln!(fmt, "| Section nodes | {} |", usize::sum(self.nodes.values().map(|s| s.len())))?;
+        writeln!(fmt, "| Left nodes    | {} |", self.left_nodes.nodes.len())?;
+        writeln!(fmt, "| Split strategy | {} |", self.params.split_strategy)?;
+        writeln!(fmt, "| Merge strategy | {} |", self.params.merge_kind())?;
+        writeln!(fmt, "| Ageing rule   | {} |", self.params.ageing)?;
+        writeln!(fmt, "| Elder selection | {} |", self.params.elder_selection)?;
+        write!(fmt, "{}", ElderRows(&self.output.elders, self.output.churn))?;
+        let stats = &self.output.relocation;
+        writeln!(fmt, "| Relocation policy | {} |", self.params.relocation)?;
+        if let Some(distance) = stats.mean_distance() {
+            writeln!(fmt, "| Mean relocation distance | {:.2} |", distance)?;
+        }
+        if let Some(variance) = stats.mean_size_variance() {
+            writeln!(fmt, "| Mean section size variance | {:.2} |", variance)?;
+        }
+        if self.params.malicious_fraction > 0.0 {
+            let malicious = self.nodes
+                .values()
+                .flat_map(|s| s.nodes())
+                .filter(|n| n.is_malicious())
+                .count();
+            let (third, half) = self.malicious_sections();
+            let iteration = |i: Option<usize>| i.map_or("never".to_owned(), |i| i.to_string());
+            writeln!(fmt, "| Malicious nodes | {} |", malicious)?;
+            writeln!(fmt, "| Sections >= 1/3 malicious | {} |", third)?;
+            writeln!(fmt, "| Sections >= 1/2 malicious | {} |", half)?;
+            writeln!(fmt, "| First 1/3 malicious | {} |", iteration(self.output.first_third_malicious))?;
+            writeln!(fmt, "| First capture | {} |", iteration(self.output.first_capture))?;
+            write!(fmt, "{}", GrindRows(&self.output.grinding))?;
+        }
+        if self.params.true_reliability {
+            let median = |m: Option<f64>| m.map_or("-".to_owned(), |m| format!("{:.2}", m));
+            writeln!(fmt, "| Median reliability | {} |", median(self.median_reliability(false)))?;
+            writeln!(fmt, "| Median elder reliability | {} |", median(self.median_reliability(true)))?;
+        }
+        writeln!(fmt)?;
+
+        if self.params.attacker != AttackerKind::None {
+            writeln!(fmt, "Attacker: {}", self.params.attacker)?;
+            writeln!(fmt, "{}", AttackTable(&self.output.attacks))?;
+        }
+
+        if self.params.scenario.is_some() {
+            writeln!(fmt, "Phases:")?;
+            writeln!(fmt, "{}", PhaseTable(&self.output.phases))?;
+        }
+
+        if !self.output.prefix_changes.is_empty() {
+            writeln!(fmt, "Splits and merges:")?;
+            writeln!(fmt, "{}", LifecycleTable(&self.output.prefix_changes))?;
+        }
+
+        if self.output.elders.changes > 0 {
+            writeln!(fmt, "Elder changes:")?;
+            writeln!(fmt, "{}", SwapTable(&self.output.elders))?;
+        }
+
+        if !self.output.failures.is_empty() {
+            writeln!(fmt, "Mass failures:")?;
+            writeln!(fmt, "{}", FailureTable(&self.output.failures, self.params.is_timed()))?;
         }
-        try!(writeln!(fmt, "| Section nodes | {} |", usize::sum(self.nodes.values().map(|s| s.len()))));
-        try!(writeln!(fmt, "| Left nodes    | {} |", self.left_nodes.len()));
-        try!(writeln!(fmt));
 
         // Distribution of sections per prefix length
         let mut distribution : BTreeMap<u8, Vec<usize>> = BTreeMap::new();
         for (pfx, section) in &self.nodes {
-            let mut entry = distribution.entry(pfx.len()).or_insert(Vec::new());
+            let entry = distribution.entry(pfx.len()).or_default();
             entry.push(section.len());
         }
         let mut lengths: Vec<u8> = distribution.keys().cloned().collect();
         lengths.sort();
-        try!(writeln!(fmt, "| Prefix len {}", Stats::get_header_line()));
-        try!(writeln!(fmt, "|:-----------{}", Stats::get_separator_line()));
+        writeln!(fmt, "| Prefix len {}", Stats::get_header_line())?;
+        writeln!(fmt, "|:-----------{}", Stats::get_separator_line())?;
         for i in lengths {
-            try!(writeln!(fmt, "| {} | {}", i, Stats::new(distribution.get(&i).unwrap())))
+            writeln!(fmt, "| {} | {}", i, Stats::new(distribution.get(&i).unwrap()))?;
         }
         writeln!(fmt, "| All | {}", Stats::new(&self.nodes.values().map(|s| s.len()).collect()))
     }
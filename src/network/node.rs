@@ -1,22 +1,32 @@
 use std::fmt;
 use serde_json;
-use random::random;
 use tiny_keccak::sha3_256;
 use network::prefix::{Name, Prefix};
 use params::DropDist;
 
 pub type Digest = [u8; 32];
 
-/// A node has a name and an age
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A node has a name and an age, and can be controlled by an attacker
 #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Node {
     name: Name,
     age: u8,
+    /// skipped when false, so that honest nodes (and the event hashes) look the same as before
+    #[serde(default, skip_serializing_if = "is_false")]
+    malicious: bool,
 }
 
 impl fmt::Debug for Node {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "Node({:?}; age={})", self.name, self.age)
+        write!(fmt, "Node({:?}; age={}", self.name, self.age)?;
+        if self.malicious {
+            write!(fmt, "; malicious")?;
+        }
+        write!(fmt, ")")
     }
 }
 
@@ -26,12 +36,22 @@ impl Node {
         Node {
             name: Name(name),
             age,
+            malicious: false,
+        }
+    }
+
+    /// Creates a new node controlled by an attacker
+    pub fn new_malicious(name: u64, age: u8) -> Node {
+        Node {
+            malicious: true,
+            ..Node::new(name, age)
         }
     }
 
-    /// Generates a relocated name and increases the age by 1
-    pub fn relocate(&mut self, prefix: &Prefix) {
-        self.name = prefix.substituted_in(Name(random()));
+    /// Moves the node to the given random name substituted into the prefix and increases the
+    /// age by 1
+    pub fn relocate(&mut self, prefix: &Prefix, random_name: Name) {
+        self.name = prefix.substituted_in(random_name);
         self.age += 1;
     }
 
@@ -52,22 +72,24 @@ impl Node {
         self.age
     }
 
+    /// Returns whether the node is controlled by an attacker
+    pub fn is_malicious(&self) -> bool {
+        self.malicious
+    }
+
     /// age ++
     pub fn increment_age(&mut self) {
-        self.age = self.age + 1
+        self.age += 1
     }
 
-    /// Returns whether the node is an Adult
-    pub fn is_adult(&self) -> bool {
-        self.age > 4
+    /// Returns whether the node is an Adult, given the minimum age of Adults
+    pub fn is_adult(&self, min_adult_age: u8) -> bool {
+        self.age >= min_adult_age
     }
 
     /// Returns the weight used in randomly choosing a node to be dropped
-    pub fn drop_probability(&self, dist: DropDist) -> f64 {
-        match dist {
-            DropDist::RevProp => 10.0 / self.age as f64,
-            DropDist::Exponential => 2.0f64.powf(-(self.age as f64)),
-        }
+    pub fn drop_probability(&self, dist: &DropDist) -> f64 {
+        dist.weight(self.age)
     }
 
     /// Returns the hash of the node struct
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::str::FromStr;
 
 /// A helper struct that only has the purpose of pretty-printing debug information
 #[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
@@ -43,6 +44,10 @@ impl Prefix {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     fn len_mask(&self) -> u64 {
         if self.len == 0 {
             0
@@ -107,15 +112,24 @@ impl Prefix {
         }
     }
 
+    /// Returns the number of leading bits the prefixes share
+    pub fn common_len(&self, other: &Prefix) -> u8 {
+        ((self.bits ^ other.bits).leading_zeros() as u8)
+            .min(self.len)
+            .min(other.len)
+    }
+
     pub fn substituted_in(&self, mut name: Name) -> Name {
         let mask = self.len_mask();
         name.0 &= !mask;
         name.0 |= self.bits;
         name
     }
+}
 
-    #[allow(unused)]
-    pub fn from_str(s: &str) -> Option<Prefix> {
+impl FromStr for Prefix {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Prefix, ()> {
         let mut prefix = Self::empty();
         for c in s.chars() {
             match c {
@@ -126,29 +140,78 @@ impl Prefix {
                     prefix = prefix.extend(1);
                 }
                 _ => {
-                    return None;
+                    return Err(());
                 }
             }
         }
-        Some(prefix)
+        Ok(prefix)
     }
+}
 
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
+impl fmt::Display for Prefix {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         for i in 0..self.len {
             let mask = 1 << (63 - i);
             if self.bits & mask == 0 {
-                result.push('0');
+                write!(fmt, "0")?;
             } else {
-                result.push('1');
+                write!(fmt, "1")?;
             }
         }
-        result
+        Ok(())
     }
 }
 
 impl fmt::Debug for Prefix {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "Prefix({})", self.to_string())
+        write!(fmt, "Prefix({})", self)
+    }
+}
+
+/// (De)serialisation of maps keyed by prefixes. Prefixes can't be keys of maps in formats such as
+/// JSON, so the maps are represented as sequences of `(prefix, value)` pairs instead.
+pub mod prefix_map {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use super::Prefix;
+
+    pub fn serialize<S, V>(map: &BTreeMap<Prefix, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        V: Serialize,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, D, V>(deserializer: D) -> Result<BTreeMap<Prefix, V>, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Deserialize<'de>,
+    {
+        let pairs: Vec<(Prefix, V)> = Vec::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+/// (De)serialisation of optional prefixes as bit strings, for the parameters
+pub mod prefix_string {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use super::Prefix;
+
+    pub fn serialize<S: Serializer>(prefix: &Option<Prefix>, serializer: S) -> Result<S::Ok, S::Error> {
+        match *prefix {
+            Some(ref prefix) => serializer.serialize_str(&prefix.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Prefix>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(s) => s.parse()
+                .map(Some)
+                .map_err(|_| D::Error::custom(format!("invalid prefix {:?}", s))),
+            None => Ok(None),
+        }
     }
 }
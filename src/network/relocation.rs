@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use network::Network;
+use network::churn::NetworkEvent;
+use network::node::Node;
+use network::prefix::{Name, Prefix};
+use params::ParamsError;
+
+/// The rules for choosing the section a relocated node moves to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RelocationKind {
+    /// the neighbour with the shortest prefix, then with the fewest nodes
+    Neighbour,
+    /// a uniformly random section
+    Random,
+    /// the section the hash of the churn event causing the relocation falls into
+    Hash,
+    /// the section with the fewest nodes in the whole network
+    Smallest,
+    /// the neighbour with the fewest adults
+    FewestAdults,
+}
+
+impl FromStr for RelocationKind {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        match s {
+            "neighbour" => Ok(RelocationKind::Neighbour),
+            "random" => Ok(RelocationKind::Random),
+            "hash" => Ok(RelocationKind::Hash),
+            "smallest" => Ok(RelocationKind::Smallest),
+            "fewest-adults" => Ok(RelocationKind::FewestAdults),
+            _ => Err(ParamsError::UnknownRelocationPolicy(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for RelocationKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RelocationKind::Neighbour => write!(fmt, "neighbour"),
+            RelocationKind::Random => write!(fmt, "random"),
+            RelocationKind::Hash => write!(fmt, "hash"),
+            RelocationKind::Smallest => write!(fmt, "smallest"),
+            RelocationKind::FewestAdults => write!(fmt, "fewest-adults"),
+        }
+    }
+}
+
+/// A way of choosing the section a relocated node moves to
+pub trait RelocationPolicy {
+    /// Returns the prefix of the section the node relocated from `source` moves to, after the
+    /// churn event `cause` triggered the relocation
+    fn target(
+        &self,
+        network: &mut Network,
+        source: Prefix,
+        node: &Node,
+        cause: NetworkEvent,
+    ) -> Prefix;
+}
+
+/// Returns the policy of the given kind
+pub fn relocation_policy(kind: RelocationKind) -> Box<dyn RelocationPolicy> {
+    match kind {
+        RelocationKind::Neighbour => Box::new(NeighbourPolicy),
+        RelocationKind::Random => Box::new(RandomPolicy),
+        RelocationKind::Hash => Box::new(HashPolicy),
+        RelocationKind::Smallest => Box::new(SmallestPolicy),
+        RelocationKind::FewestAdults => Box::new(FewestAdultsPolicy),
+    }
+}
+
+/// Relocates to the neighbour with the least peers as per the document, preferring shorter
+/// prefixes
+struct NeighbourPolicy;
+
+impl RelocationPolicy for NeighbourPolicy {
+    fn target(
+        &self,
+        network: &mut Network,
+        source: Prefix,
+        _node: &Node,
+        _cause: NetworkEvent,
+    ) -> Prefix {
+        network
+            .sections()
+            .iter()
+            .filter(|&(pfx, _)| pfx.is_neighbour(&source))
+            .min_by_key(|&(pfx, section)| (pfx.len(), section.len()))
+            .map_or(source, |(pfx, _)| *pfx)
+    }
+}
+
+/// Relocates to any section with equal probability
+struct RandomPolicy;
+
+impl RelocationPolicy for RandomPolicy {
+    fn target(
+        &self,
+        network: &mut Network,
+        _source: Prefix,
+        _node: &Node,
+        _cause: NetworkEvent,
+    ) -> Prefix {
+        let count = network.num_sections();
+        let index = network.rng_mut().random_range(0, count);
+        *network.sections().keys().nth(index).unwrap()
+    }
+}
+
+/// Relocates to the section whose prefix matches the hash of the churn event that triggered the
+/// relocation, so that the target can be verified by anyone, but not chosen by the relocated node
+struct HashPolicy;
+
+impl RelocationPolicy for HashPolicy {
+    fn target(
+        &self,
+        network: &mut Network,
+        source: Prefix,
+        _node: &Node,
+        cause: NetworkEvent,
+    ) -> Prefix {
+        let digest = cause.hash();
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        let name = Name(u64::from_be_bytes(bytes));
+        network
+            .sections()
+            .keys()
+            .find(|pfx| pfx.matches(name))
+            .cloned()
+            .unwrap_or(source)
+    }
+}
+
+/// Relocates to the section with the fewest nodes anywhere in the network
+struct SmallestPolicy;
+
+impl RelocationPolicy for SmallestPolicy {
+    fn target(
+        &self,
+        network: &mut Network,
+        source: Prefix,
+        _node: &Node,
+        _cause: NetworkEvent,
+    ) -> Prefix {
+        network
+            .sections()
+            .iter()
+            .min_by_key(|&(_, section)| section.len())
+            .map_or(source, |(pfx, _)| *pfx)
+    }
+}
+
+/// Relocates to the neighbour with the fewest adults, which needs new adults the most
+struct FewestAdultsPolicy;
+
+impl RelocationPolicy for FewestAdultsPolicy {
+    fn target(
+        &self,
+        network: &mut Network,
+        source: Prefix,
+        _node: &Node,
+        _cause: NetworkEvent,
+    ) -> Prefix {
+        let min_adult_age = network.params().min_adult_age;
+        network
+            .sections()
+            .iter()
+            .filter(|&(pfx, _)| pfx.is_neighbour(&source))
+            .min_by_key(|&(_, section)| {
+                section
+                    .nodes()
+                    .iter()
+                    .filter(|n| n.is_adult(min_adult_age))
+                    .count()
+            })
+            .map_or(source, |(pfx, _)| *pfx)
+    }
+}
+
+/// Returns the distance between two sections in prefix bits: the number of bits of the longer
+/// prefix after the ones the prefixes share. It's 0 for the same section and 1 for siblings.
+pub fn distance(a: Prefix, b: Prefix) -> u8 {
+    let common = a.common_len(&b);
+    a.len().max(b.len()) - common
+}
+
+/// The distances of the relocations and the spread of the section sizes they lead to
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RelocationStats {
+    /// the number of relocations by distance in prefix bits
+    pub distances: BTreeMap<u8, u64>,
+    /// the sum of the variances of the section sizes at the end of every iteration
+    pub size_variance_sum: f64,
+    /// the number of iterations summed up in `size_variance_sum`
+    pub iterations: u64,
+}
+
+impl RelocationStats {
+    /// Records a relocation between the given sections
+    pub fn record_relocation(&mut self, source: Prefix, target: Prefix) {
+        *self.distances.entry(distance(source, target)).or_insert(0) += 1;
+    }
+
+    /// Records the section sizes at the end of an iteration
+    pub fn record_sizes(&mut self, sizes: &[usize]) {
+        if sizes.is_empty() {
+            return;
+        }
+        let n = sizes.len() as f64;
+        let mean = sizes.iter().sum::<usize>() as f64 / n;
+        let variance = sizes
+            .iter()
+            .map(|&size| (size as f64 - mean).powi(2))
+            .sum::<f64>() / n;
+        self.size_variance_sum += variance;
+        self.iterations += 1;
+    }
+
+    /// Returns the mean distance of the relocations in prefix bits
+    pub fn mean_distance(&self) -> Option<f64> {
+        let count: u64 = self.distances.values().sum();
+        if count == 0 {
+            return None;
+        }
+        let total: u64 = self.distances
+            .iter()
+            .map(|(&distance, &n)| distance as u64 * n)
+            .sum();
+        Some(total as f64 / count as f64)
+    }
+
+    /// Returns the variance of the section sizes, averaged over the iterations
+    pub fn mean_size_variance(&self) -> Option<f64> {
+        if self.iterations == 0 {
+            None
+        } else {
+            Some(self.size_variance_sum / self.iterations as f64)
+        }
+    }
+}
@@ -1,44 +1,33 @@
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
-use network::{BUFFER, GROUP_SIZE};
 use network::prefix::{Name, Prefix};
-use network::node::{Digest, Node};
+use network::ageing::ageing_rule;
+use network::elders::elder_selection;
+use network::split::{merge_policy, split_policy};
+use network::node::Node;
 use network::churn::{NetworkEvent, SectionEvent};
+use network::invariants::Violation;
 use params::Params;
+use random::SeededRng;
 
 /// An enum for return values of some methods.
 /// The methods can say that the event was ignored, in which case its processing ends as if nothing
 /// ever happened. If the event was handled, it could generate some additional response to the
 /// network.
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum EventResult {
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum EventResult {
     Handled,
     HandledWithEvent(SectionEvent),
     Ignored,
 }
 
-/// Returns the number of trailing zeros in a hash
-fn trailing_zeros(hash: Digest) -> u8 {
-    let mut result = 0;
-    let mut byte_index = 31;
-    loop {
-        let zeros = hash[byte_index].trailing_zeros();
-        result += zeros;
-        if zeros < 8 || byte_index == 0 {
-            break;
-        }
-        byte_index -= 1;
-    }
-    result as u8
-}
-
 /// A section after a split together with events it needs to process afterwards.
 pub type SplitData = (Section, Vec<NetworkEvent>);
 
 /// The structure representing a section.
 /// It has a prefix and some nodes. The nodes are sorted into categories: Elders, Adults and
 /// Infants, according to their age an function in the section.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Section {
     /// the section's prefix
     prefix: Prefix,
@@ -58,6 +47,10 @@ pub struct Section {
     merging: bool,
     /// are we currently splitting?
     splitting: bool,
+    /// the number of churn events every node has seen in the section, if the ageing rule counts
+    /// them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    churn_counts: BTreeMap<Name, u64>,
 }
 
 impl Section {
@@ -72,6 +65,7 @@ impl Section {
             infants: BTreeSet::new(),
             merging: false,
             splitting: false,
+            churn_counts: BTreeMap::new(),
         }
     }
 
@@ -80,44 +74,115 @@ impl Section {
         self.nodes.len()
     }
 
-    /// Returns the list of nodes in the section sorted by age.
-    fn nodes_by_age(&self) -> Vec<Node> {
-        let mut by_age: Vec<_> = self.nodes.iter().map(|(_, n)| *n).collect();
+    /// Returns whether the node with the given name is in the section
+    pub fn contains(&self, name: Name) -> bool {
+        self.nodes.contains_key(&name)
+    }
+
+    /// Returns whether there are no nodes in the section
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the list of nodes in the section sorted by age, from the oldest.
+    pub fn nodes_by_age(&self) -> Vec<Node> {
+        let mut by_age: Vec<_> = self.nodes.values().copied().collect();
         by_age.sort_by_key(|x| -(x.age() as i8));
         by_age
     }
 
     /// Returns whether the section has a complete group.
-    /// A complete group is GROUP_SIZE nodes that are Adults (have age > 4)
-    pub fn is_complete(&self) -> bool {
-        self.elders.len() == 8
+    /// A complete group is `group_size` nodes that are Adults (have age >= `min_adult_age`)
+    pub fn is_complete(&self, params: &Params) -> bool {
+        self.elders.len() == params.group_size
             && self.elders
                 .iter()
                 .filter_map(|x| self.nodes.get(x))
-                .all(|n| n.is_adult())
+                .all(|n| n.is_adult(params.min_adult_age))
+    }
+
+    /// Returns the names of the nodes that should be the Elders of the section
+    fn choose_elders(&self, params: &Params) -> BTreeSet<Name> {
+        elder_selection(params.elder_selection).choose(self, params)
     }
 
     /// Updates the names of the Elders in the section
-    fn update_elders(&mut self) {
-        let by_age = self.nodes_by_age();
-        self.elders = by_age
-            .into_iter()
-            .take(GROUP_SIZE)
-            .filter(|n| n.is_adult())
+    fn update_elders(&mut self, params: &Params) {
+        self.elders = self.choose_elders(params);
+    }
+
+    /// Returns whether a node with the given name can belong to the section
+    pub fn accepts(&self, name: Name) -> bool {
+        self.verifying_prefix.matches(name)
+    }
+
+    /// Checks that the nodes match the verifying prefix and that the Elders, Adults and Infants
+    /// agree with the nodes
+    pub fn check_invariants(&self, params: &Params) -> Result<(), Violation> {
+        if let Some(node) = self.nodes.values().find(|n| !self.accepts(n.name())) {
+            return Err(Violation::ForeignNode(self.prefix, *node));
+        }
+        let adults: BTreeSet<_> = self.nodes
+            .values()
+            .filter(|n| n.is_adult(params.min_adult_age))
             .map(|n| n.name())
             .collect();
+        let infants: BTreeSet<_> = self.nodes
+            .keys()
+            .filter(|name| !adults.contains(name))
+            .cloned()
+            .collect();
+        if adults != self.adults {
+            return Err(Violation::Categories(self.prefix, "adults", adults, self.adults.clone()));
+        }
+        if infants != self.infants {
+            return Err(Violation::Categories(self.prefix, "infants", infants, self.infants.clone()));
+        }
+        let elders = self.choose_elders(params);
+        if elders != self.elders {
+            return Err(Violation::Elders(self.prefix, elders, self.elders.clone()));
+        }
+        Ok(())
+    }
+
+    /// Returns a detailed description of the section's state, for debugging
+    pub fn dump(&self) -> String {
+        let mut result = format!(
+            "Section {:?} (verifying: {:?}, merging: {}, splitting: {})\n",
+            self.prefix, self.verifying_prefix, self.merging, self.splitting
+        );
+        for node in self.nodes.values() {
+            let name = node.name();
+            result.push_str(&format!(
+                "\t{:?}{}{}{}\n",
+                node,
+                if self.elders.contains(&name) { " elder" } else { "" },
+                if self.adults.contains(&name) { " adult" } else { "" },
+                if self.infants.contains(&name) { " infant" } else { "" },
+            ));
+        }
+        let unknown: Vec<_> = self.elders
+            .iter()
+            .chain(&self.adults)
+            .chain(&self.infants)
+            .filter(|name| !self.nodes.contains_key(name))
+            .collect();
+        if !unknown.is_empty() {
+            result.push_str(&format!("\tnames without nodes: {:?}\n", unknown));
+        }
+        result
     }
 
-    /// Processes a network event passed to the section and responds with appropriate section
-    /// events
-    pub fn handle_event(&mut self, event: NetworkEvent, params: &Params) -> Vec<SectionEvent> {
-        let mut events = vec![];
-        let other_event = match event {
+    /// Applies the direct effect of a network event to the section, without ageing
+    fn apply_event(&mut self, event: NetworkEvent, params: &Params) -> EventResult {
+        match event {
             NetworkEvent::Live(node, _) => self.add(node, params),
-            NetworkEvent::Relocated(node) | NetworkEvent::Gone(node) => self.relocate(node.name()),
-            NetworkEvent::Lost(name) => self.remove(name),
+            NetworkEvent::Relocated(node) | NetworkEvent::Gone(node) => {
+                self.relocate(node.name(), params)
+            }
+            NetworkEvent::Lost(name) => self.remove(name, params),
             NetworkEvent::PrefixChange(p) => {
-                println!("{:?} PrefixChange to {:?}", self.prefix, p);
+                log!(params, "{:?} PrefixChange to {:?}", self.prefix, p);
                 EventResult::Handled
             }
             NetworkEvent::StartMerge(prefix) => {
@@ -125,7 +190,8 @@ impl Section {
                     // in order to accept new nodes, we must know that we are merging
                     self.verifying_prefix = prefix;
                     self.merging = true;
-                    println!(
+                    log!(
+                        params,
                         "MERGE: {:?} (verifying: {:?}) StartMerge({:?})",
                         self.prefix, self.verifying_prefix, prefix
                     );
@@ -134,95 +200,116 @@ impl Section {
                     EventResult::Ignored
                 }
             }
-        };
+        }
+    }
+
+    /// Processes a network event passed to the section and responds with appropriate section
+    /// events, together with what happened to the event itself
+    pub fn handle_event(
+        &mut self,
+        event: NetworkEvent,
+        params: &Params,
+        rng: &mut SeededRng,
+    ) -> (EventResult, Vec<SectionEvent>) {
+        let mut events = vec![];
+        let other_event = self.apply_event(event, params);
         if self.should_merge(params) {
             self.merging = true;
             events.push(SectionEvent::RequestMerge);
         }
         if self.should_split(params) {
             self.splitting = true;
-            println!("{:?} Requesting a split", self.prefix);
+            log!(params, "{:?} Requesting a split", self.prefix);
             events.push(SectionEvent::RequestSplit);
         }
         match other_event {
             EventResult::Handled => {
-                events.extend(self.check_ageing(event, params));
+                events.extend(self.check_ageing(event, params, rng));
             }
             EventResult::HandledWithEvent(ev) => {
-                events.extend(self.check_ageing(event, params));
+                events.extend(self.check_ageing(event, params, rng));
                 events.push(ev);
             }
             EventResult::Ignored => (),
         }
-        events
+        (other_event, events)
     }
 
-    /// Return the node that should be relocated, with age no greater than `age`
-    fn choose_for_relocation(&self, age: u8) -> Option<Node> {
-        let by_age: Vec<_> = self.nodes_by_age()
-            .into_iter()
-            .filter(|n| n.age() <= age)
-            .collect();
-        let candidates = by_age.first().cloned().map(|n| {
-            by_age
-                .into_iter()
-                .filter(|m| m.age() == n.age())
-                .collect::<Vec<_>>()
-        });
-        candidates.and_then(|mut cand| {
-            if cand.len() <= 1 {
-                cand.first().cloned()
-            } else {
-                let total_xor = cand.iter().fold(0, |total, node| total ^ node.name().0);
-                cand.sort_by_key(|node| node.name().0 ^ total_xor);
-                cand.first().cloned()
-            }
-        })
-    }
-
-    /// Checks the hash of the NetworkEvent and returns any SectionEvents triggered by it due to
-    /// node ageing - in particular, relocations
-    fn check_ageing(&mut self, event: NetworkEvent, params: &Params) -> Vec<SectionEvent> {
+    /// Returns any SectionEvents triggered by the NetworkEvent due to node ageing - in
+    /// particular, relocations chosen by the ageing rule
+    fn check_ageing(
+        &mut self,
+        event: NetworkEvent,
+        params: &Params,
+        rng: &mut SeededRng,
+    ) -> Vec<SectionEvent> {
         if !event.should_count() {
             return vec![];
         }
-        let event_hash = event.hash();
-        let trailing_zeros = trailing_zeros(event_hash);
-        let node_to_age = self.choose_for_relocation(trailing_zeros + params.init_age);
+        let node_to_age = ageing_rule(params.ageing).relocation(self, event, params, Some(rng));
         if let Some(node) = node_to_age {
-            let _ = self.relocate(node.name());
+            let _ = self.relocate(node.name(), params);
             vec![SectionEvent::NeedRelocate(node)]
         } else {
             vec![]
         }
     }
 
+    /// Returns the node that would be relocated due to ageing if the section handled the event,
+    /// if the ageing rule allows predicting it
+    pub fn relocation_after(&self, event: NetworkEvent, params: &Params) -> Option<Node> {
+        if !event.should_count() {
+            return None;
+        }
+        let params = &Params {
+            verbose: false,
+            ..params.clone()
+        };
+        let mut section = self.clone();
+        match section.apply_event(event, params) {
+            EventResult::Ignored => None,
+            _ => ageing_rule(params.ageing).relocation(&mut section, event, params, None),
+        }
+    }
+
+    /// Counts a churn event for every node in the section
+    pub fn count_churn(&mut self) {
+        for name in self.nodes.keys() {
+            *self.churn_counts.entry(*name).or_insert(0) += 1;
+        }
+    }
+
+    /// Returns the number of churn events the node has seen in the section
+    pub fn churn_count(&self, name: Name) -> u64 {
+        self.churn_counts.get(&name).cloned().unwrap_or(0)
+    }
+
     /// Adds a node to the section and returns whether the event was handled
     fn add(&mut self, node: Node, params: &Params) -> EventResult {
         if params.max_young != 0 && node.age() == params.init_age
             && self.nodes.values().filter(|n| n.age() <= params.init_age).count() >= params.max_young
-            && self.is_complete()
+            && self.is_complete(params)
         {
             // disallow more than one node aged 1 per section if the section is complete
             // (all elders are adults)
-            println!("Node {:?} refused in section {:?}", node, self.prefix);
+            log!(params, "Node {:?} refused in section {:?}", node, self.prefix);
             return EventResult::HandledWithEvent(SectionEvent::NodeRejected(node));
         }
         assert!(
-            self.verifying_prefix.matches(node.name()),
+            self.accepts(node.name()),
             "Section {:?}: {:?} does not match {:?}!",
             self.prefix,
             node.name(),
             self.verifying_prefix
         );
-        if node.is_adult() {
+        if node.is_adult(params.min_adult_age) {
             self.adults.insert(node.name());
         } else {
             self.infants.insert(node.name());
         }
         self.nodes.insert(node.name(), node);
-        self.update_elders();
-        if !node.is_adult() && self.is_complete() {
+        self.update_elders(params);
+        if !node.is_adult(params.min_adult_age) && self.is_complete(params) {
             EventResult::Ignored
         } else {
             EventResult::Handled
@@ -230,13 +317,14 @@ impl Section {
     }
 
     /// Removes a node from the section and returns whether the event was handled
-    fn remove(&mut self, name: Name) -> EventResult {
+    fn remove(&mut self, name: Name, params: &Params) -> EventResult {
         let node = self.nodes.remove(&name);
         let _ = self.adults.remove(&name);
         let _ = self.infants.remove(&name);
-        self.update_elders();
+        let _ = self.churn_counts.remove(&name);
+        self.update_elders(params);
         if let Some(node) = node {
-            if !node.is_adult() && self.is_complete() {
+            if !node.is_adult(params.min_adult_age) && self.is_complete(params) {
                 EventResult::Ignored
             } else {
                 EventResult::HandledWithEvent(SectionEvent::NodeDropped(node))
@@ -248,13 +336,14 @@ impl Section {
 
     /// Relocates a node from the section - that is, removes it, but doesn't generate a `Dropped`
     /// section event, which would cause the network to think that the node has actually left
-    fn relocate(&mut self, name: Name) -> EventResult {
+    fn relocate(&mut self, name: Name, params: &Params) -> EventResult {
         let node = self.nodes.remove(&name);
         let _ = self.adults.remove(&name);
         let _ = self.infants.remove(&name);
-        self.update_elders();
+        let _ = self.churn_counts.remove(&name);
+        self.update_elders(params);
         if let Some(node) = node {
-            if !node.is_adult() && self.is_complete() {
+            if !node.is_adult(params.min_adult_age) && self.is_complete(params) {
                 EventResult::Ignored
             } else {
                 EventResult::Handled
@@ -275,7 +364,8 @@ impl Section {
         let mut churn0 = vec![];
         let mut churn1 = vec![];
         let (prefix0, prefix1) = (self.prefix.extend(0), self.prefix.extend(1));
-        println!(
+        log!(
+            params,
             "Splitting {:?} into {:?} and {:?}",
             self.prefix, prefix0, prefix1
         );
@@ -284,7 +374,7 @@ impl Section {
         section0.verifying_prefix = prefix0;
         section1.prefix = prefix1;
         section1.verifying_prefix = prefix1;
-        for (name, mut node) in &mut section0.nodes {
+        for (name, node) in &mut section0.nodes {
             if params.inc_age {
                 node.increment_age();
             }
@@ -325,7 +415,9 @@ impl Section {
         if merged_prefix.len() < result.verifying_prefix.len() {
             result.verifying_prefix = merged_prefix;
         }
-        for (_, mut node) in self.nodes.into_iter().chain(other.nodes.into_iter()) {
+        result.churn_counts = self.churn_counts;
+        result.churn_counts.extend(other.churn_counts);
+        for (_, mut node) in self.nodes.into_iter().chain(other.nodes) {
             if params.inc_age {
                 node.increment_age();
             }
@@ -334,57 +426,62 @@ impl Section {
         result
     }
 
-    fn count_adults(&self, prefix: &Prefix) -> usize {
+    /// Returns the number of Adults in the section
+    pub fn num_adults(&self) -> usize {
+        self.adults.len()
+    }
+
+    /// Returns the number of Adults matching the prefix
+    pub fn count_adults(&self, prefix: &Prefix) -> usize {
         self.adults.iter().filter(|&n| prefix.matches(*n)).count()
     }
 
-    fn count_peers(&self, prefix: &Prefix) -> usize {
-        if self.is_complete() {
+    /// Returns the number of nodes of any age matching the prefix
+    pub fn count_nodes(&self, prefix: &Prefix) -> usize {
+        self.nodes.keys().filter(|&n| prefix.matches(*n)).count()
+    }
+
+    /// Returns the number of Adults matching the prefix if the section is complete, or the number
+    /// of all the nodes matching it otherwise
+    pub fn count_peers(&self, prefix: &Prefix, params: &Params) -> usize {
+        if self.is_complete(params) {
             self.count_adults(prefix)
         } else {
-            self.nodes
-                .iter()
-                .filter(|&(n, _)| prefix.matches(*n))
-                .count()
+            self.count_nodes(prefix)
         }
     }
 
-    /// Returns whether the section should split. If we are already splitting, returns false
+    /// Returns whether the section should split according to the split strategy. If we are
+    /// already splitting, returns false
     pub fn should_split(&self, params: &Params) -> bool {
-        use params::Strategy::*;
-        let prefix0 = self.prefix.extend(0);
-        let prefix1 = self.prefix.extend(1);
-        let count_prefix = match params.split_strategy {
-            Complete => Self::count_adults,
-            Always => Self::count_peers,
-        };
-        let count0 = count_prefix(self, &prefix0);
-        let count1 = count_prefix(self, &prefix1);
-        !self.merging && !self.splitting && count0 >= GROUP_SIZE + BUFFER
-            && count1 >= GROUP_SIZE + BUFFER
+        !self.merging && !self.splitting
+            && split_policy(params.split_strategy).should_split(self, params)
     }
 
-    /// Returns whether the section should merge. If we are already merging, returns false
+    /// Returns whether the section should merge according to the merge strategy. If we are
+    /// already merging, returns false
     pub fn should_merge(&self, params: &Params) -> bool {
-        use params::Strategy::*;
-        match params.split_strategy {
-            Complete => {
-                !self.merging && !self.splitting && self.prefix.len() > 0
-                    && self.adults.len() <= GROUP_SIZE
-            }
-            Always => {
-                !self.merging && !self.splitting && self.prefix.len() > 0 && if self.is_complete() {
-                    self.adults.len() <= GROUP_SIZE
-                } else {
-                    self.nodes.len() <= GROUP_SIZE
-                }
-            }
-        }
+        !self.merging && !self.splitting && !self.prefix.is_empty()
+            && merge_policy(params.merge_kind()).should_merge(self, params)
     }
 
     /// Returns a set of all the nodes in the section
     pub fn nodes(&self) -> BTreeSet<Node> {
-        self.nodes.iter().map(|(_, n)| *n).collect()
+        self.nodes.values().copied().collect()
+    }
+
+    /// Returns the number of the section's Elders controlled by an attacker
+    pub fn malicious_elders(&self) -> usize {
+        self.elders
+            .iter()
+            .filter_map(|name| self.nodes.get(name))
+            .filter(|node| node.is_malicious())
+            .count()
+    }
+
+    /// Returns the names of the section's Elders
+    pub fn elder_names(&self) -> &BTreeSet<Name> {
+        &self.elders
     }
 
     /// Returns the section's Elders as `Node`s
@@ -0,0 +1,187 @@
+use std::fmt;
+use std::str::FromStr;
+use network::prefix::Prefix;
+use network::section::Section;
+use params::{Params, ParamsError, Strategy};
+
+/// A way of deciding whether a section should split
+pub trait SplitPolicy {
+    /// Returns whether the section is large enough to split into its two children
+    fn should_split(&self, section: &Section, params: &Params) -> bool;
+}
+
+/// Returns the split policy of the given strategy
+pub fn split_policy(strategy: Strategy) -> Box<dyn SplitPolicy> {
+    match strategy {
+        Strategy::Always => Box::new(PeersSplit),
+        Strategy::Complete => Box::new(AdultsSplit),
+        Strategy::Count => Box::new(CountSplit),
+        Strategy::CompleteChildren => Box::new(CompleteChildrenSplit),
+        Strategy::Buffered => Box::new(BufferedSplit),
+    }
+}
+
+/// Returns whether both children of the section have at least `min_count` nodes, as counted by
+/// `count`
+fn both_children<F: Fn(&Prefix) -> usize>(section: &Section, min_count: usize, count: F) -> bool {
+    let prefix = section.prefix();
+    count(&prefix.extend(0)) >= min_count && count(&prefix.extend(1)) >= min_count
+}
+
+/// Both children need `group_size + buffer` Adults if the section is complete, or that many
+/// nodes of any age if it isn't
+struct PeersSplit;
+
+impl SplitPolicy for PeersSplit {
+    fn should_split(&self, section: &Section, params: &Params) -> bool {
+        both_children(section, params.group_size + params.buffer, |prefix| {
+            section.count_peers(prefix, params)
+        })
+    }
+}
+
+/// Both children need `group_size + buffer` Adults
+struct AdultsSplit;
+
+impl SplitPolicy for AdultsSplit {
+    fn should_split(&self, section: &Section, params: &Params) -> bool {
+        both_children(section, params.group_size + params.buffer, |prefix| {
+            section.count_adults(prefix)
+        })
+    }
+}
+
+/// Both children need `group_size + buffer` nodes, regardless of their age
+struct CountSplit;
+
+impl SplitPolicy for CountSplit {
+    fn should_split(&self, section: &Section, params: &Params) -> bool {
+        both_children(section, params.group_size + params.buffer, |prefix| {
+            section.count_nodes(prefix)
+        })
+    }
+}
+
+/// Both children need just enough Adults for a complete group, without a buffer
+struct CompleteChildrenSplit;
+
+impl SplitPolicy for CompleteChildrenSplit {
+    fn should_split(&self, section: &Section, params: &Params) -> bool {
+        both_children(section, params.group_size, |prefix| section.count_adults(prefix))
+    }
+}
+
+/// Both children need `group_size` Adults plus the buffer for their prefix length
+struct BufferedSplit;
+
+impl SplitPolicy for BufferedSplit {
+    fn should_split(&self, section: &Section, params: &Params) -> bool {
+        let buffer = params.buffer_for(section.prefix().len() + 1);
+        both_children(section, params.group_size + buffer, |prefix| {
+            section.count_adults(prefix)
+        })
+    }
+}
+
+/// The rules for deciding whether a section should merge with its sibling
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeKind {
+    /// at most `group_size` Adults
+    Adults,
+    /// at most `group_size` Adults if the section is complete, or at most `group_size` nodes of
+    /// any age if it isn't
+    Peers,
+    /// at most `group_size` nodes, regardless of their age
+    Count,
+    /// too few Adults for a complete group
+    Incomplete,
+}
+
+impl MergeKind {
+    /// Returns the merge rule that goes with the split strategy
+    pub fn matching(strategy: Strategy) -> MergeKind {
+        match strategy {
+            Strategy::Always => MergeKind::Peers,
+            Strategy::Complete | Strategy::Buffered => MergeKind::Adults,
+            Strategy::Count => MergeKind::Count,
+            Strategy::CompleteChildren => MergeKind::Incomplete,
+        }
+    }
+}
+
+impl FromStr for MergeKind {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        match s {
+            "adults" => Ok(MergeKind::Adults),
+            "peers" => Ok(MergeKind::Peers),
+            "count" => Ok(MergeKind::Count),
+            "incomplete" => Ok(MergeKind::Incomplete),
+            _ => Err(ParamsError::UnknownMergeStrategy(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for MergeKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeKind::Adults => write!(fmt, "adults"),
+            MergeKind::Peers => write!(fmt, "peers"),
+            MergeKind::Count => write!(fmt, "count"),
+            MergeKind::Incomplete => write!(fmt, "incomplete"),
+        }
+    }
+}
+
+/// A way of deciding whether a section should merge with its sibling
+pub trait MergePolicy {
+    /// Returns whether the section has become too small to stay on its own
+    fn should_merge(&self, section: &Section, params: &Params) -> bool;
+}
+
+/// Returns the merge policy of the given kind
+pub fn merge_policy(kind: MergeKind) -> Box<dyn MergePolicy> {
+    match kind {
+        MergeKind::Adults => Box::new(AdultsMerge),
+        MergeKind::Peers => Box::new(PeersMerge),
+        MergeKind::Count => Box::new(CountMerge),
+        MergeKind::Incomplete => Box::new(IncompleteMerge),
+    }
+}
+
+struct AdultsMerge;
+
+impl MergePolicy for AdultsMerge {
+    fn should_merge(&self, section: &Section, params: &Params) -> bool {
+        section.num_adults() <= params.group_size
+    }
+}
+
+struct PeersMerge;
+
+impl MergePolicy for PeersMerge {
+    fn should_merge(&self, section: &Section, params: &Params) -> bool {
+        if section.is_complete(params) {
+            section.num_adults() <= params.group_size
+        } else {
+            section.len() <= params.group_size
+        }
+    }
+}
+
+struct CountMerge;
+
+impl MergePolicy for CountMerge {
+    fn should_merge(&self, section: &Section, params: &Params) -> bool {
+        section.len() <= params.group_size
+    }
+}
+
+struct IncompleteMerge;
+
+impl MergePolicy for IncompleteMerge {
+    fn should_merge(&self, section: &Section, params: &Params) -> bool {
+        section.num_adults() < params.group_size
+    }
+}
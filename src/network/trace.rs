@@ -0,0 +1,61 @@
+use std::io::{self, Write};
+use serde_json;
+use network::prefix::Prefix;
+use network::node::Node;
+use network::churn::{ExternalEvent, NetworkEvent, SectionEvent};
+use network::section::EventResult;
+use params::Params;
+
+/// The version of the trace format, recorded in the header
+pub const TRACE_VERSION: u64 = 2;
+
+/// The first line of a trace file: everything needed to replay the trace apart from the records
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceHeader {
+    pub version: u64,
+    pub params: Params,
+}
+
+/// An event that went through the network: a churn event coming from the outside, a churn event
+/// delivered to a section, a section event reported back to the network, or a relocation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TracedEvent {
+    External(ExternalEvent),
+    Network(NetworkEvent),
+    Section(SectionEvent),
+    /// a node relocated from the section, before and after the relocation, and the section it
+    /// moves to
+    Relocation(Node, Node, Prefix),
+}
+
+/// A single record of the event trace
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    /// the iteration during which the event was processed
+    pub iteration: usize,
+    /// the prefix of the section the event was delivered to, or reported by
+    pub prefix: Prefix,
+    /// the event itself
+    pub event: TracedEvent,
+    /// what happened to the event
+    pub result: EventResult,
+}
+
+/// Writes the header line of a trace
+pub fn write_header<W: Write>(writer: &mut W, params: &Params) -> io::Result<()> {
+    let header = TraceHeader {
+        version: TRACE_VERSION,
+        params: params.clone(),
+    };
+    serde_json::to_writer(&mut *writer, &header)?;
+    writeln!(writer)
+}
+
+/// Writes the records as JSON lines - one record per line
+pub fn write_records<W: Write>(writer: &mut W, records: &[TraceRecord]) -> io::Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut *writer, record)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
@@ -1,40 +1,381 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{Error as DeError, MapAccess, Visitor};
+use serde::de::value::MapAccessDeserializer;
+use serde::ser::SerializeStruct;
+use serde_json;
+use toml;
+use availability::Availability;
+use clock::is_valid_schedule;
+use random::Seed;
+use scenario::Scenario;
+use session::SessionDist;
+use network::ageing::AgeingKind;
+use network::attack::{AttackerKind, GrindGoal};
+use network::elders::ElderKind;
+use network::failure::{sort_schedule, ScheduledFailure};
+use network::relocation::RelocationKind;
+use network::split::MergeKind;
+use network::prefix::{prefix_string, Prefix};
+use network::{BUFFER, GROUP_SIZE, MIN_ADULT_AGE};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Strategy {
     Always,
     Complete,
+    /// both children need enough nodes, whatever their age
+    Count,
+    /// both children need enough Adults for a complete group, without a buffer
+    CompleteChildren,
+    /// both children need enough Adults, with a buffer depending on the prefix length
+    Buffered,
 }
 
 impl FromStr for Strategy {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Self, ()> {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
         match s {
             "always" => Ok(Strategy::Always),
             "complete" => Ok(Strategy::Complete),
-            _ => Err(()),
+            "count" => Ok(Strategy::Count),
+            "complete-children" => Ok(Strategy::CompleteChildren),
+            "buffered" => Ok(Strategy::Buffered),
+            _ => Err(ParamsError::UnknownSplitStrategy(s.to_owned())),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+impl fmt::Display for Strategy {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Strategy::Always => write!(fmt, "always"),
+            Strategy::Complete => write!(fmt, "complete"),
+            Strategy::Count => write!(fmt, "count"),
+            Strategy::CompleteChildren => write!(fmt, "complete-children"),
+            Strategy::Buffered => write!(fmt, "buffered"),
+        }
+    }
+}
+
+/// The weights of the nodes by age in choosing the one to be dropped.
+/// Written as `exponential`, `reverse-proportional`, `uniform`, `linear:M`, `power:K` or
+/// `table:FILE`; a table is serialised with its weights, so that the file isn't read again.
+#[derive(Clone, PartialEq)]
 pub enum DropDist {
+    /// 2^-age
     Exponential,
+    /// 10 / age
     RevProp,
+    /// the same for every age
+    Uniform,
+    /// decreasing linearly from M at age 1 to 1 at age M, and staying at 1 for older nodes
+    Linear(u8),
+    /// age^-K
+    PowerLaw(f64),
+    /// the weights listed by age in a file
+    Table(String, Arc<BTreeMap<u8, f64>>),
+}
+
+impl DropDist {
+    /// Returns the weight of a node of the given age
+    pub fn weight(&self, age: u8) -> f64 {
+        match *self {
+            DropDist::Exponential => 2.0f64.powf(-(age as f64)),
+            DropDist::RevProp => 10.0 / age as f64,
+            DropDist::Uniform => 1.0,
+            DropDist::Linear(max_age) => max_age.saturating_sub(age).saturating_add(1) as f64,
+            DropDist::PowerLaw(exponent) => (age as f64).powf(-exponent),
+            DropDist::Table(_, ref weights) => weights
+                .range(..=age)
+                .next_back()
+                .or_else(|| weights.iter().next())
+                .map_or(0.0, |(_, &weight)| weight),
+        }
+    }
+}
+
+/// Reads the weights of a drop distribution table from a file: an age and a weight on every line,
+/// separated by whitespace or a comma, with lines starting with `#` skipped. Ages missing from the
+/// table take the weight of the closest younger age listed.
+fn load_weights(file: &str) -> Result<BTreeMap<u8, f64>, ParamsError> {
+    let mut contents = String::new();
+    let _ = File::open(file)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|err| ParamsError::Io(file.to_owned(), err))?;
+    let mut weights = BTreeMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || ParamsError::Format(file.to_owned(), format!("line {}: expected an age and a weight", i + 1));
+        let fields: Vec<_> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|field| !field.is_empty())
+            .collect();
+        if fields.len() != 2 {
+            return Err(invalid());
+        }
+        let age = fields[0].parse().map_err(|_| invalid())?;
+        let weight = fields[1].parse().map_err(|_| invalid())?;
+        let _ = weights.insert(age, weight);
+    }
+    check_weights(file, &weights)?;
+    Ok(weights)
+}
+
+/// Checks that the weights of a table are finite and not negative, and that some are positive
+fn check_weights(file: &str, weights: &BTreeMap<u8, f64>) -> Result<(), ParamsError> {
+    if let Some((age, _)) = weights
+        .iter()
+        .find(|&(_, &weight)| !(weight >= 0.0 && weight.is_finite()))
+    {
+        return Err(ParamsError::Format(file.to_owned(), format!("invalid weight of age {}", age)));
+    }
+    if !weights.values().any(|&weight| weight > 0.0) {
+        return Err(ParamsError::Format(file.to_owned(), "no positive weights".to_owned()));
+    }
+    Ok(())
+}
+
+/// The weight of an age in a drop distribution table, as it is serialised
+#[derive(Serialize, Deserialize)]
+struct AgeWeight {
+    age: u8,
+    weight: f64,
+}
+
+/// A drop distribution table as it is serialised: the file it was loaded from and its weights
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LoadedTable {
+    table: String,
+    weights: Vec<AgeWeight>,
 }
 
 impl FromStr for DropDist {
-    type Err = ();
-    fn from_str(s: &str) -> Result<Self, ()> {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        let invalid = || ParamsError::InvalidValue("drop distribution", s.to_owned());
+        if let Some(file) = s.strip_prefix("table:") {
+            return Ok(DropDist::Table(file.to_owned(), Arc::new(load_weights(file)?)));
+        }
+        if let Some(max_age) = s.strip_prefix("linear:") {
+            return max_age
+                .parse()
+                .ok()
+                .filter(|&max_age| max_age > 0)
+                .map(DropDist::Linear)
+                .ok_or_else(invalid);
+        }
+        if let Some(exponent) = s.strip_prefix("power:") {
+            return exponent
+                .parse()
+                .ok()
+                .filter(|exponent: &f64| exponent.is_finite())
+                .map(DropDist::PowerLaw)
+                .ok_or_else(invalid);
+        }
         match s {
             "exp" | "exponential" => Ok(DropDist::Exponential),
             "rev" | "reverse-proportional" => Ok(DropDist::RevProp),
-            _ => Err(()),
+            "uniform" => Ok(DropDist::Uniform),
+            _ => Err(ParamsError::UnknownDropDist(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for DropDist {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DropDist::Exponential => write!(fmt, "exponential"),
+            DropDist::RevProp => write!(fmt, "reverse-proportional"),
+            DropDist::Uniform => write!(fmt, "uniform"),
+            DropDist::Linear(max_age) => write!(fmt, "linear:{}", max_age),
+            DropDist::PowerLaw(exponent) => write!(fmt, "power:{}", exponent),
+            DropDist::Table(ref file, _) => write!(fmt, "table:{}", file),
+        }
+    }
+}
+
+/// Shows the table file without the weights
+impl fmt::Debug for DropDist {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DropDist::Exponential => write!(fmt, "Exponential"),
+            DropDist::RevProp => write!(fmt, "RevProp"),
+            DropDist::Uniform => write!(fmt, "Uniform"),
+            DropDist::Linear(max_age) => write!(fmt, "Linear({})", max_age),
+            DropDist::PowerLaw(exponent) => write!(fmt, "PowerLaw({})", exponent),
+            DropDist::Table(ref file, _) => write!(fmt, "Table({:?})", file),
+        }
+    }
+}
+
+impl Serialize for DropDist {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            DropDist::Table(ref file, ref weights) => {
+                let weights: Vec<_> = weights
+                    .iter()
+                    .map(|(&age, &weight)| AgeWeight { age, weight })
+                    .collect();
+                let mut table = serializer.serialize_struct("DropDist", 2)?;
+                table.serialize_field("table", file)?;
+                table.serialize_field("weights", &weights)?;
+                table.end()
+            }
+            _ => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DropDist {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_loaded(deserializer, |table: LoadedTable| {
+            let weights = table
+                .weights
+                .into_iter()
+                .map(|AgeWeight { age, weight }| (age, weight))
+                .collect();
+            check_weights(&table.table, &weights)?;
+            Ok(DropDist::Table(table.table, Arc::new(weights)))
+        })
+    }
+}
+
+/// The way the simulation advances
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    /// a fixed number of iterations with one random add, drop or rejoin each
+    Discrete,
+    /// a simulated clock with Poisson joins and nodes leaving at the end of their sessions
+    Continuous,
+}
+
+impl FromStr for Engine {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        match s {
+            "discrete" => Ok(Engine::Discrete),
+            "continuous" => Ok(Engine::Continuous),
+            _ => Err(ParamsError::InvalidValue("engine", s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Engine::Discrete => write!(fmt, "discrete"),
+            Engine::Continuous => write!(fmt, "continuous"),
+        }
+    }
+}
+
+/// Errors in loading, saving or validating the parameters
+#[derive(Debug)]
+pub enum ParamsError {
+    /// the config file couldn't be read or written
+    Io(String, io::Error),
+    /// the config file couldn't be parsed or serialised
+    Format(String, String),
+    /// a value couldn't be parsed
+    InvalidValue(&'static str, String),
+    UnknownSplitStrategy(String),
+    UnknownDropDist(String),
+    UnknownAttacker(String),
+    UnknownGrindGoal(String),
+    UnknownRelocationPolicy(String),
+    UnknownAgeingRule(String),
+    UnknownElderSelection(String),
+    UnknownMergeStrategy(String),
+    /// the add and drop probabilities add up to more than 100
+    Probabilities(u8, u8),
+    /// the seed is all zeros
+    ZeroSeed,
+    /// the group size is 0
+    ZeroGroupSize,
+    /// a fraction isn't between 0 and 1
+    InvalidFraction(&'static str, f64),
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParamsError::Io(ref file, ref err) => write!(fmt, "{}: {}", file, err),
+            ParamsError::Format(ref file, ref err) => write!(fmt, "{}: {}", file, err),
+            ParamsError::InvalidValue(param, ref value) => {
+                write!(fmt, "invalid value of {}: {:?}", param, value)
+            }
+            ParamsError::UnknownSplitStrategy(ref strategy) => write!(
+                fmt,
+                "unknown split strategy {:?}; expected always/complete/count/complete-children/buffered",
+                strategy
+            ),
+            ParamsError::UnknownDropDist(ref dist) => write!(
+                fmt,
+                "unknown drop distribution {:?}; expected exp/exponential/rev/reverse-proportional/uniform/linear:M/power:K/table:FILE",
+                dist
+            ),
+            ParamsError::UnknownAttacker(ref attacker) => write!(
+                fmt,
+                "unknown attacker {:?}; expected none/targeted/rejoin/stubborn",
+                attacker
+            ),
+            ParamsError::UnknownGrindGoal(ref goal) => write!(
+                fmt,
+                "unknown grinding goal {:?}; expected \"trigger\" or \"avoid\"",
+                goal
+            ),
+            ParamsError::UnknownRelocationPolicy(ref policy) => write!(
+                fmt,
+                "unknown relocation policy {:?}; expected \"neighbour\", \"random\", \"hash\", \"smallest\" or \"fewest-adults\"",
+                policy
+            ),
+            ParamsError::UnknownAgeingRule(ref rule) => write!(
+                fmt,
+                "unknown ageing rule {:?}; expected \"hash\", \"counter\" or \"probabilistic\"",
+                rule
+            ),
+            ParamsError::UnknownElderSelection(ref selection) => write!(
+                fmt,
+                "unknown elder selection {:?}; expected oldest/oldest-xor/hysteresis:K/weighted",
+                selection
+            ),
+            ParamsError::UnknownMergeStrategy(ref strategy) => write!(
+                fmt,
+                "unknown merge strategy {:?}; expected adults/peers/count/incomplete",
+                strategy
+            ),
+            ParamsError::Probabilities(add, drop) => write!(
+                fmt,
+                "add and drop probabilities must add up to at most 100, got {} + {}",
+                add, drop
+            ),
+            ParamsError::ZeroSeed => write!(fmt, "the seed must not be all zeros"),
+            ParamsError::ZeroGroupSize => write!(fmt, "the group size must be at least 1"),
+            ParamsError::InvalidFraction(param, value) => {
+                write!(fmt, "{} must be between 0 and 1, got {}", param, value)
+            }
         }
     }
 }
 
-#[derive(Clone, Debug)]
+impl Error for ParamsError {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Params {
     pub init_age: u8,
     pub split_strategy: Strategy,
@@ -42,6 +383,321 @@ pub struct Params {
     pub iterations: usize,
     pub growth: (u8, u8),
     pub structure_output_file: Option<String>,
+    /// The CSV file the splits and merges are written to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifecycle_output_file: Option<String>,
     pub drop_dist: DropDist,
     pub inc_age: bool,
+    /// The seed of the simulation's RNG; a random one is chosen if `None`
+    pub seed: Option<Seed>,
+    /// The number of Elders in a complete section
+    pub group_size: usize,
+    /// The number of nodes above `group_size` both halves need to have for a section to split
+    pub buffer: usize,
+    /// The buffers of the `buffered` split strategy by the prefix length of the halves, starting
+    /// with length 1; longer prefixes use the last one, and `buffer` is used if there are none
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub split_buffers: Vec<usize>,
+    /// The rule for deciding whether a section should merge; the one matching the split strategy
+    /// if `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_strategy: Option<MergeKind>,
+    /// The minimum age of an Adult
+    pub min_adult_age: u8,
+    /// Whether every event should be logged to the standard output
+    pub verbose: bool,
+    /// Whether the consistency of the network should be checked after every iteration
+    pub check_invariants: bool,
+    /// The fraction of joining nodes that are controlled by an attacker
+    pub malicious_fraction: f64,
+    /// The behaviour of the malicious nodes
+    pub attacker: AttackerKind,
+    /// The section the targeted attacker tries to get into; the one in which it holds the most
+    /// elders if `None`
+    #[serde(with = "prefix_string", skip_serializing_if = "Option::is_none")]
+    pub attack_target: Option<Prefix>,
+    /// The number of names a joining malicious node tries before choosing the one that serves
+    /// `grind_goal` best; 0 or 1 means no grinding
+    pub grind_budget: u32,
+    /// What the malicious nodes grind their names for
+    pub grind_goal: GrindGoal,
+    /// The way the simulation advances
+    pub engine: Engine,
+    /// The simulated time the continuous-time engine runs for
+    pub duration: f64,
+    /// The rate of joins in the continuous-time engine, as `(time, rate)` points interpolated
+    /// linearly
+    pub join_rate: Vec<(f64, f64)>,
+    /// The distribution of the session lengths in the continuous-time engine, and of the
+    /// nodes' true reliabilities
+    pub session: SessionDist,
+    /// Whether the nodes dropped in the discrete engine are chosen by their true reliability,
+    /// a session length drawn when they first join, rather than by their age
+    pub true_reliability: bool,
+    /// The availability trace whose joins and leaves drive the simulation instead of the engine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub availability: Option<Availability>,
+    /// The rule for choosing the section a relocated node moves to
+    pub relocation: RelocationKind,
+    /// The rule for choosing the nodes that age after a churn event
+    pub ageing: AgeingKind,
+    /// The rule for choosing the Elders of a section among its Adults
+    pub elder_selection: ElderKind,
+    /// The mass failures to happen during the simulation, sorted by the point at which they
+    /// happen
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<ScheduledFailure>,
+    /// The phases the run is divided into, each with its own churn; the run ends with the last
+    /// one instead of after `iterations`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scenario: Option<Scenario>,
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        Params {
+            init_age: 1,
+            split_strategy: Strategy::Complete,
+            max_young: 1,
+            iterations: 100_000,
+            growth: (90, 7),
+            structure_output_file: None,
+            lifecycle_output_file: None,
+            drop_dist: DropDist::Exponential,
+            inc_age: false,
+            seed: None,
+            group_size: GROUP_SIZE,
+            buffer: BUFFER,
+            split_buffers: vec![],
+            merge_strategy: None,
+            min_adult_age: MIN_ADULT_AGE,
+            verbose: true,
+            check_invariants: false,
+            malicious_fraction: 0.0,
+            attacker: AttackerKind::None,
+            attack_target: None,
+            grind_budget: 0,
+            grind_goal: GrindGoal::Trigger,
+            engine: Engine::Discrete,
+            duration: 10_000.0,
+            join_rate: vec![(0.0, 1.0)],
+            session: SessionDist::Exponential(1000.0),
+            true_reliability: false,
+            availability: None,
+            relocation: RelocationKind::Neighbour,
+            ageing: AgeingKind::Hash,
+            elder_selection: ElderKind::Oldest,
+            failures: vec![],
+            scenario: None,
+        }
+    }
+}
+
+/// Returns whether the file should be treated as JSON (as opposed to TOML or CSV), by its
+/// extension
+pub fn is_json(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+/// Deserialises a value loaded from a file either from the path of the file, as written in a
+/// config file or on the command line, or from the contents loaded from it, as it is serialised,
+/// so that checkpoints and traces don't depend on the file
+pub fn deserialize_loaded<'de, D, T, L, F>(deserializer: D, from_loaded: F) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = ParamsError>,
+    L: Deserialize<'de>,
+    F: FnOnce(L) -> Result<T, ParamsError>,
+{
+    struct LoadedVisitor<T, L, F>(F, PhantomData<(T, L)>);
+
+    impl<'de, T, L, F> Visitor<'de> for LoadedVisitor<T, L, F>
+    where
+        T: FromStr<Err = ParamsError>,
+        L: Deserialize<'de>,
+        F: FnOnce(L) -> Result<T, ParamsError>,
+    {
+        type Value = T;
+
+        fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+            write!(fmt, "a file name or the contents loaded from it")
+        }
+
+        fn visit_str<E: DeError>(self, s: &str) -> Result<T, E> {
+            s.parse().map_err(|err: ParamsError| E::custom(err.to_string()))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<T, A::Error> {
+            let loaded = L::deserialize(MapAccessDeserializer::new(map))?;
+            (self.0)(loaded).map_err(|err| A::Error::custom(err.to_string()))
+        }
+    }
+
+    deserializer.deserialize_any(LoadedVisitor(from_loaded, PhantomData))
+}
+
+impl Params {
+    /// Returns whether the simulation runs in simulated time, as opposed to iterations
+    pub fn is_timed(&self) -> bool {
+        self.engine == Engine::Continuous || self.availability.is_some()
+    }
+
+    /// Loads the parameters from a TOML or JSON file (depending on the extension) and validates
+    /// them. Parameters missing from the file get their default values.
+    pub fn from_file(file: &str) -> Result<Params, ParamsError> {
+        let mut contents = String::new();
+        File::open(file)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|err| ParamsError::Io(file.to_owned(), err))?;
+        let mut params: Params = if is_json(file) {
+            serde_json::from_str(&contents)
+                .map_err(|err| ParamsError::Format(file.to_owned(), err.to_string()))?
+        } else {
+            toml::from_str(&contents)
+                .map_err(|err| ParamsError::Format(file.to_owned(), err.to_string()))?
+        };
+        params.validate()?;
+        sort_schedule(&mut params.failures);
+        Ok(params)
+    }
+
+    /// Saves the parameters to a TOML or JSON file (depending on the extension), so that they can
+    /// be loaded back with `from_file`
+    pub fn save(&self, file: &str) -> Result<(), ParamsError> {
+        let contents = if is_json(file) {
+            serde_json::to_string_pretty(self)
+                .map_err(|err| ParamsError::Format(file.to_owned(), err.to_string()))?
+        } else {
+            // through a `Value`, which puts the tables, e.g. a drop distribution table, after the
+            // plain values as TOML requires
+            toml::Value::try_from(self)
+                .and_then(|value| toml::to_string(&value))
+                .map_err(|err| ParamsError::Format(file.to_owned(), err.to_string()))?
+        };
+        File::create(file)
+            .and_then(|mut f| f.write_all(contents.as_bytes()))
+            .map_err(|err| ParamsError::Io(file.to_owned(), err))
+    }
+
+    /// Returns the buffer the halves of a split need above `group_size`, given their prefix
+    /// length, for the `buffered` split strategy. The halves are at least 1 bit long, so the
+    /// first buffer is the one for length 1.
+    pub fn buffer_for(&self, prefix_len: u8) -> usize {
+        self.split_buffers
+            .get((prefix_len as usize).saturating_sub(1))
+            .or_else(|| self.split_buffers.last())
+            .cloned()
+            .unwrap_or(self.buffer)
+    }
+
+    /// Returns the merge rule in use
+    pub fn merge_kind(&self) -> MergeKind {
+        self.merge_strategy
+            .unwrap_or_else(|| MergeKind::matching(self.split_strategy))
+    }
+
+    /// Checks whether the parameters make sense together
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        let (p_add, p_drop) = self.growth;
+        if p_add as u16 + p_drop as u16 > 100 {
+            return Err(ParamsError::Probabilities(p_add, p_drop));
+        }
+        if self.seed.is_some_and(|seed| seed.iter().all(|&x| x == 0)) {
+            return Err(ParamsError::ZeroSeed);
+        }
+        if self.group_size == 0 {
+            return Err(ParamsError::ZeroGroupSize);
+        }
+        if !is_valid_schedule(&self.join_rate) {
+            return Err(ParamsError::InvalidValue("join_rate", format!("{:?}", self.join_rate)));
+        }
+        self.session.validate()?;
+        if !(0.0..=1.0).contains(&self.malicious_fraction) {
+            return Err(ParamsError::InvalidFraction("malicious_fraction", self.malicious_fraction));
+        }
+        if let Some(ref scenario) = self.scenario {
+            let invalid = |what: String| ParamsError::Format(scenario.to_string(), what);
+            if self.availability.is_some() {
+                return Err(invalid("can't be combined with an availability trace".to_owned()));
+            }
+            // the churn of a timed run comes from the session lengths, not the probabilities
+            if self.is_timed() {
+                for phase in scenario.phases() {
+                    if phase.growth.is_some() || phase.drop_dist.is_some() {
+                        return Err(invalid(format!(
+                            "phase {:?} sets growth or drop_dist, which have no effect in simulated time",
+                            phase.name
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+    use super::*;
+
+    /// Writes the contents to a file in the temporary directory and returns its path
+    fn write_temp(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("ageing_sim_{}_{}", name, process::id()));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn drop_dist_round_trip() {
+        for s in &["exponential", "reverse-proportional", "uniform", "linear:10", "power:1.5"] {
+            let dist: DropDist = s.parse().unwrap();
+            assert_eq!(dist.to_string(), *s);
+        }
+        assert_eq!("exp".parse::<DropDist>().unwrap(), DropDist::Exponential);
+        assert_eq!("rev".parse::<DropDist>().unwrap(), DropDist::RevProp);
+    }
+
+    #[test]
+    fn invalid_drop_dists() {
+        for s in &["", "x", "linear:0", "linear:x", "power:x", "power:inf", "table:"] {
+            assert!(s.parse::<DropDist>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn weights_table() {
+        let file = write_temp("weights", "# age weight\n1 0\n\n2,3\n10 0.5\n");
+        let weights = load_weights(&file).unwrap();
+        assert_eq!(weights.into_iter().collect::<Vec<_>>(), vec![(1, 0.0), (2, 3.0), (10, 0.5)]);
+
+        let dist: DropDist = format!("table:{}", file).parse().unwrap();
+        assert_eq!(dist.to_string(), format!("table:{}", file));
+        assert_eq!(dist.weight(1), 0.0);
+        assert_eq!(dist.weight(9), 3.0);
+        assert_eq!(dist.weight(20), 0.5);
+        fs::remove_file(&file).unwrap();
+
+        // the weights are serialised with the table, so the file isn't needed to read it back
+        let json = serde_json::to_string(&dist).unwrap();
+        assert_eq!(serde_json::from_str::<DropDist>(&json).unwrap(), dist);
+    }
+
+    #[test]
+    fn invalid_weights_tables() {
+        for (i, contents) in ["1 0\n2 0\n", "", "1\n", "1 2 3\n", "x 1\n", "1 -1\n", "1 inf\n"]
+            .iter()
+            .enumerate()
+        {
+            let file = write_temp(&format!("invalid_weights_{}", i), contents);
+            assert!(load_weights(&file).is_err(), "{:?}", contents);
+            fs::remove_file(&file).unwrap();
+        }
+        let json = r#"{"table": "weights.csv", "weights": [{"age": 1, "weight": 0.0}]}"#;
+        assert!(serde_json::from_str::<DropDist>(json).is_err());
+    }
 }
@@ -1,69 +1,141 @@
-use rand::{self, thread_rng, Rand, Rng, SeedableRng, XorShiftRng};
+use rand::{self, thread_rng, Rand, Rng};
 use rand::distributions::range::SampleRange;
-use std::cell::RefCell;
-use std::env;
 
-thread_local! {
-    static SEED: [u32; 4] = match env::var("AGE_SEED") {
-        Ok(value) => {
-            let nums: Vec<u32> = value.split(|c| c == '[' || c == ']' || c == ' ' || c == ',')
-                                      .filter_map(|s| s.parse().ok())
-                                      .collect();
-            assert_eq!(nums.len(), 4, "AGE_SEED {} isn't in the form '[1, 2, 3, 4]'.", value);
-            [nums[0], nums[1], nums[2], nums[3]]
-        }
-        Err(_) => {
-            let mut rng = thread_rng();
-            [rng.next_u32().wrapping_add(rng.next_u32()),
-             rng.next_u32().wrapping_add(rng.next_u32()),
-             rng.next_u32().wrapping_add(rng.next_u32()),
-             rng.next_u32().wrapping_add(rng.next_u32())]
-        }
-    };
+/// The seed of a simulation's random number generator
+pub type Seed = [u32; 4];
+
+/// Generates a fresh seed from the thread RNG.
+pub fn random_seed() -> Seed {
+    let mut rng = thread_rng();
+    [
+        rng.next_u32().wrapping_add(rng.next_u32()),
+        rng.next_u32().wrapping_add(rng.next_u32()),
+        rng.next_u32().wrapping_add(rng.next_u32()),
+        rng.next_u32().wrapping_add(rng.next_u32()),
+    ]
+}
 
-    static WEAK_RNG: RefCell<XorShiftRng> = RefCell::new(
-        SEED.with(|seed| {
-            println!("Seed: {:?}", seed);
-            XorShiftRng::from_seed(*seed)
+/// Generates `count` seeds for separate runs: derived from `seed` if given, so that the runs are
+/// reproducible, or random otherwise.
+pub fn seeds(seed: Option<Seed>, count: usize) -> Vec<Seed> {
+    let mut rng = seed.map(SeededRng::new);
+    (0..count)
+        .map(|_| loop {
+            let seed = match rng {
+                Some(ref mut rng) => rng.random(),
+                None => random_seed(),
+            };
+            if seed.iter().any(|&x| x != 0) {
+                break seed;
+            }
         })
-    );
+        .collect()
 }
 
-/// Get the seed used for the random number generator.
-#[allow(unused)]
-pub fn seed() -> [u32; 4] {
-    SEED.with(|seed| *seed)
+/// Parses a seed in the form '[1, 2, 3, 4]' (the brackets are optional).
+/// Returns `None` if there aren't exactly 4 numbers, or if they are all zeros.
+pub fn parse_seed(value: &str) -> Option<Seed> {
+    let nums: Vec<u32> = value
+        .split(['[', ']', ' ', ','])
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    if nums.len() != 4 || nums.iter().all(|&n| n == 0) {
+        return None;
+    }
+    Some([nums[0], nums[1], nums[2], nums[3]])
 }
 
-/// Random value from the thread-local weak RNG.
-pub fn random<T: Rand>() -> T {
-    WEAK_RNG.with(|rng| rng.borrow_mut().gen())
+/// The Xorshift algorithm, producing the same stream as `rand::XorShiftRng`, but with a state
+/// that can be saved and restored.
+#[derive(Clone, Serialize, Deserialize)]
+struct XorShift {
+    x: u32,
+    y: u32,
+    z: u32,
+    w: u32,
 }
 
-/// Random value from a range from the thread-local weak RNG.
-pub fn random_range<T: Rand + PartialOrd + SampleRange>(min: T, max: T) -> T {
-    WEAK_RNG.with(|rng| rng.borrow_mut().gen_range(min, max))
+impl XorShift {
+    /// Creates a new generator. This will panic if `seed` is entirely 0.
+    fn from_seed(seed: Seed) -> XorShift {
+        assert!(
+            !seed.iter().all(|&x| x == 0),
+            "XorShift::from_seed called with an all zero seed."
+        );
+        XorShift {
+            x: seed[0],
+            y: seed[1],
+            z: seed[2],
+            w: seed[3],
+        }
+    }
 }
 
-/// Sample values from an iterator.
-#[allow(unused)]
-pub fn sample<T, I>(iterable: I, amount: usize) -> Vec<T>
-where
-    I: IntoIterator<Item = T>,
-{
-    WEAK_RNG.with(|rng| rand::sample(&mut *rng.borrow_mut(), iterable, amount))
+impl Rng for XorShift {
+    fn next_u32(&mut self) -> u32 {
+        let x = self.x;
+        let t = x ^ (x << 11);
+        self.x = self.y;
+        self.y = self.z;
+        self.z = self.w;
+        let w = self.w;
+        self.w = w ^ (w >> 19) ^ (t ^ (t >> 8));
+        self.w
+    }
 }
 
-/// Sample a single value from an iterator.
-#[allow(unused)]
-pub fn sample_single<T, I>(iterable: I) -> Option<T>
-where
-    I: IntoIterator<Item = T>,
-{
-    sample(iterable, 1).pop()
+/// A weak, seeded RNG owned by a single simulation, so that every simulation is reproducible
+/// on its own.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SeededRng {
+    seed: Seed,
+    rng: XorShift,
 }
 
-/// Shuffle the mutable slice in place.
-pub fn shuffle<T>(values: &mut [T]) {
-    WEAK_RNG.with(|rng| rng.borrow_mut().shuffle(values))
+impl SeededRng {
+    /// Creates a new RNG from the given seed
+    pub fn new(seed: Seed) -> SeededRng {
+        SeededRng {
+            seed,
+            rng: XorShift::from_seed(seed),
+        }
+    }
+
+    /// Get the seed used for the random number generator.
+    pub fn seed(&self) -> Seed {
+        self.seed
+    }
+
+    /// Random value.
+    pub fn random<T: Rand>(&mut self) -> T {
+        self.rng.gen()
+    }
+
+    /// Random value from a range.
+    pub fn random_range<T: Rand + PartialOrd + SampleRange>(&mut self, min: T, max: T) -> T {
+        self.rng.gen_range(min, max)
+    }
+
+    /// Sample values from an iterator.
+    #[allow(unused, deprecated)]
+    pub fn sample<T, I>(&mut self, iterable: I, amount: usize) -> Vec<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        rand::sample(&mut self.rng, iterable, amount)
+    }
+
+    /// Sample a single value from an iterator.
+    #[allow(unused)]
+    pub fn sample_single<T, I>(&mut self, iterable: I) -> Option<T>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.sample(iterable, 1).pop()
+    }
+
+    /// Shuffle the mutable slice in place.
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        self.rng.shuffle(values)
+    }
 }
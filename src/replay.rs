@@ -0,0 +1,182 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead};
+use serde_json;
+use network::Network;
+use network::ageing::AgeingKind;
+use network::trace::{TraceHeader, TraceRecord, TracedEvent, TRACE_VERSION};
+
+/// The first point at which the replayed run differs from the recording
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    /// the iteration in which the difference was found
+    pub iteration: usize,
+    /// the position of the differing record among the records of the iteration
+    pub index: usize,
+    /// the recorded event, if any
+    pub expected: Option<TraceRecord>,
+    /// the event produced by the replay, if any
+    pub actual: Option<TraceRecord>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            fmt,
+            "Divergence in iteration {}, record {}:",
+            self.iteration, self.index
+        )?;
+        writeln!(fmt, "  recorded: {:?}", self.expected)?;
+        write!(fmt, "  replayed: {:?}", self.actual)
+    }
+}
+
+/// Errors that can happen when reading a trace to be replayed
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// a line of the trace couldn't be parsed
+    Format(usize, serde_json::Error),
+    /// the trace has no header line
+    MissingHeader,
+    /// the trace was written with an unsupported format version
+    Version(u64),
+    /// the records aren't sorted by iteration
+    OutOfOrder(usize),
+    /// the trace was recorded with an ageing rule whose random choices can't be replayed
+    RandomAgeing(AgeingKind),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Io(ref err) => write!(fmt, "I/O error: {}", err),
+            ReplayError::Format(line, ref err) => write!(fmt, "line {}: {}", line, err),
+            ReplayError::MissingHeader => write!(fmt, "the trace has no header"),
+            ReplayError::Version(version) => write!(
+                fmt,
+                "unsupported trace version {} (expected {})",
+                version, TRACE_VERSION
+            ),
+            ReplayError::OutOfOrder(line) => {
+                write!(fmt, "line {}: records aren't sorted by iteration", line)
+            }
+            ReplayError::RandomAgeing(kind) => write!(
+                fmt,
+                "the {} ageing rule makes random choices the trace doesn't record",
+                kind
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+/// Replays a recorded trace: the recorded external events are fed into a fresh network created
+/// with the recorded parameters, and the resulting cascade of events is compared against the
+/// recording. Relocations reuse the recorded names and target sections, so no randomness is
+/// involved.
+/// Returns the network in its final state (or at the point of divergence) and the first
+/// divergence, if any.
+pub fn replay<R: BufRead>(reader: R) -> Result<(Network, Option<Divergence>), ReplayError> {
+    let mut lines = reader.lines();
+    let header: TraceHeader = match lines.next() {
+        Some(line) => serde_json::from_str(&line?).map_err(|err| ReplayError::Format(1, err))?,
+        None => return Err(ReplayError::MissingHeader),
+    };
+    if header.version != TRACE_VERSION {
+        return Err(ReplayError::Version(header.version));
+    }
+    if header.params.ageing.is_random() {
+        return Err(ReplayError::RandomAgeing(header.params.ageing));
+    }
+    let mut network = Network::new(header.params);
+    network.set_tracing(true);
+
+    let mut recorded: Vec<TraceRecord> = vec![];
+    for (index, line) in lines.enumerate() {
+        let line_number = index + 2;
+        let record: TraceRecord =
+            serde_json::from_str(&line?).map_err(|err| ReplayError::Format(line_number, err))?;
+        let iteration = recorded.first().map(|r| r.iteration);
+        if let Some(iteration) = iteration {
+            if record.iteration < iteration {
+                return Err(ReplayError::OutOfOrder(line_number));
+            }
+            if record.iteration > iteration {
+                if let Some(divergence) = replay_iteration(&mut network, iteration, &recorded) {
+                    return Ok((network, Some(divergence)));
+                }
+                recorded.clear();
+            }
+        }
+        recorded.push(record);
+    }
+    if let Some(iteration) = recorded.first().map(|r| r.iteration) {
+        if let Some(divergence) = replay_iteration(&mut network, iteration, &recorded) {
+            return Ok((network, Some(divergence)));
+        }
+    }
+    let iterations = network.params().iterations;
+    if let Some(divergence) = replay_iteration(&mut network, iterations, &[]) {
+        return Ok((network, Some(divergence)));
+    }
+    Ok((network, None))
+}
+
+/// Processes the iterations with no recorded events up to `iteration`, then feeds the external
+/// events recorded in `iteration` into the network and compares the outcome with the records.
+fn replay_iteration(
+    network: &mut Network,
+    iteration: usize,
+    recorded: &[TraceRecord],
+) -> Option<Divergence> {
+    while network.iteration() < iteration {
+        let current = network.iteration();
+        network.process_events();
+        if let Some(divergence) = compare(current, &[], &network.take_trace()) {
+            return Some(divergence);
+        }
+    }
+    if recorded.is_empty() {
+        return None;
+    }
+    network.push_relocation_names(recorded.iter().filter_map(|record| match record.event {
+        TracedEvent::Relocation(_, relocated, _) => Some(relocated.name()),
+        _ => None,
+    }));
+    network.push_relocation_targets(recorded.iter().filter_map(|record| match record.event {
+        TracedEvent::Relocation(_, _, target) => Some(target),
+        _ => None,
+    }));
+    for record in recorded {
+        if let TracedEvent::External(event) = record.event {
+            network.apply(event);
+        }
+    }
+    network.process_events();
+    compare(iteration, recorded, &network.take_trace())
+}
+
+/// Returns the first difference between the recorded and the replayed records of an iteration
+fn compare(
+    iteration: usize,
+    expected: &[TraceRecord],
+    actual: &[TraceRecord],
+) -> Option<Divergence> {
+    (0..expected.len().max(actual.len()))
+        .map(|index| (index, expected.get(index), actual.get(index)))
+        .find(|&(_, expected, actual)| expected != actual)
+        .map(|(index, expected, actual)| Divergence {
+            iteration,
+            index,
+            expected: expected.cloned(),
+            actual: actual.cloned(),
+        })
+}
@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use network::{Network, NetworkStructure, Output};
+use stats::{Estimate, Stats};
+
+/// The results of a single replicate needed for the aggregation
+#[derive(Clone)]
+pub struct Replicate {
+    output: Output,
+    sections: usize,
+    complete: usize,
+    section_nodes: usize,
+    left_nodes: usize,
+    age_distribution: BTreeMap<u8, usize>,
+    /// the sizes of the sections, by prefix length
+    section_sizes: BTreeMap<u8, Vec<usize>>,
+}
+
+impl Replicate {
+    pub fn new(network: &Network) -> Replicate {
+        let mut section_sizes: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+        for (prefix, section) in network.sections() {
+            section_sizes.entry(prefix.len()).or_default().push(section.len());
+        }
+        Replicate {
+            output: network.output().clone(),
+            sections: network.num_sections(),
+            complete: network.complete_sections(),
+            section_nodes: network.sections().values().map(|s| s.len()).sum(),
+            left_nodes: network.left_nodes().len(),
+            age_distribution: network.age_distribution(),
+            section_sizes,
+        }
+    }
+
+    fn metrics(&self) -> [f64; 10] {
+        [
+            self.output.adds as f64,
+            self.output.drops as f64,
+            self.output.rejoins as f64,
+            self.output.relocations as f64,
+            self.output.rejections as f64,
+            self.output.churn as f64,
+            self.sections as f64,
+            self.complete as f64,
+            self.section_nodes as f64,
+            self.left_nodes as f64,
+        ]
+    }
+
+    fn all_section_sizes(&self) -> Vec<usize> {
+        self.section_sizes.values().flat_map(|s| s.iter().cloned()).collect()
+    }
+}
+
+const METRICS: [&str; 10] = [
+    "Adds",
+    "Drops",
+    "Rejoins",
+    "Relocations",
+    "Rejections",
+    "Churns",
+    "Sections",
+    "Complete",
+    "Section nodes",
+    "Left nodes",
+];
+
+/// The estimated network structure at one iteration
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StructureEstimate {
+    pub size: Estimate,
+    pub sections: Estimate,
+    pub complete: Estimate,
+    pub third_malicious: Estimate,
+    pub half_malicious: Estimate,
+}
+
+/// The estimated section size statistics for one prefix length
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatsEstimate {
+    pub count: Estimate,
+    pub average: Estimate,
+    pub min: Estimate,
+    pub max: Estimate,
+    pub standard_deviation: Estimate,
+}
+
+impl StatsEstimate {
+    /// Estimates the statistics from the section sizes of every replicate. Replicates without
+    /// any sections count as 0 sections, and are otherwise left out.
+    fn new<'a, I: Iterator<Item = Option<&'a Vec<usize>>>>(sizes: I) -> StatsEstimate {
+        let mut columns: [Vec<f64>; 5] = Default::default();
+        for sizes in sizes {
+            let stats = match sizes {
+                Some(sizes) if !sizes.is_empty() => Stats::new(sizes),
+                _ => {
+                    columns[0].push(0.0);
+                    continue;
+                }
+            };
+            columns[0].push(stats.count() as f64);
+            columns[1].push(stats.average());
+            columns[2].push(stats.min() as f64);
+            columns[3].push(stats.max() as f64);
+            if let Some(standard_deviation) = stats.standard_deviation() {
+                columns[4].push(standard_deviation);
+            }
+        }
+        StatsEstimate {
+            count: Estimate::new(&columns[0]),
+            average: Estimate::new(&columns[1]),
+            min: Estimate::new(&columns[2]),
+            max: Estimate::new(&columns[3]),
+            standard_deviation: Estimate::new(&columns[4]),
+        }
+    }
+
+    fn csv_fields(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.count.csv_fields(),
+            self.average.csv_fields(),
+            self.min.csv_fields(),
+            self.max.csv_fields(),
+            self.standard_deviation.csv_fields()
+        )
+    }
+}
+
+/// Means and 95% confidence intervals of the results of several replicates of a simulation
+pub struct Aggregate {
+    pub replicates: usize,
+    pub metrics: Vec<(&'static str, Estimate)>,
+    pub age_distribution: BTreeMap<u8, Estimate>,
+    pub drops_dist: BTreeMap<u8, Estimate>,
+    pub network_structure: Vec<StructureEstimate>,
+    /// the section size statistics by prefix length
+    pub section_sizes: BTreeMap<u8, StatsEstimate>,
+    /// the section size statistics of all the sections
+    pub all_section_sizes: StatsEstimate,
+}
+
+/// Estimates every value of the distributions; values missing from a replicate count as 0
+fn aggregate_distributions<'a, I>(dists: I) -> BTreeMap<u8, Estimate>
+where
+    I: Iterator<Item = &'a BTreeMap<u8, usize>> + Clone,
+{
+    let keys: BTreeSet<u8> = dists.clone().flat_map(|dist| dist.keys().cloned()).collect();
+    keys.into_iter()
+        .map(|key| {
+            let values: Vec<f64> = dists
+                .clone()
+                .map(|dist| dist.get(&key).cloned().unwrap_or(0) as f64)
+                .collect();
+            (key, Estimate::new(&values))
+        })
+        .collect()
+}
+
+impl Aggregate {
+    pub fn new(replicates: &[Replicate]) -> Aggregate {
+        let metrics = METRICS
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| {
+                let values: Vec<f64> = replicates.iter().map(|r| r.metrics()[i]).collect();
+                (name, Estimate::new(&values))
+            })
+            .collect();
+
+        let iterations = replicates
+            .iter()
+            .map(|r| r.output.network_structure.len())
+            .max()
+            .unwrap_or(0);
+        let network_structure = (0..iterations)
+            .map(|i| {
+                let structures: Vec<_> = replicates
+                    .iter()
+                    .filter_map(|r| r.output.network_structure.get(i))
+                    .collect();
+                let estimate = |f: &dyn Fn(&NetworkStructure) -> usize| {
+                    let values: Vec<f64> = structures.iter().map(|s| f(s) as f64).collect();
+                    Estimate::new(&values)
+                };
+                StructureEstimate {
+                    size: estimate(&|s| s.size),
+                    sections: estimate(&|s| s.sections),
+                    complete: estimate(&|s| s.complete),
+                    third_malicious: estimate(&|s| s.third_malicious),
+                    half_malicious: estimate(&|s| s.half_malicious),
+                }
+            })
+            .collect();
+
+        let prefix_lengths: BTreeSet<u8> = replicates
+            .iter()
+            .flat_map(|r| r.section_sizes.keys().cloned())
+            .collect();
+        let section_sizes = prefix_lengths
+            .into_iter()
+            .map(|len| {
+                let sizes = replicates.iter().map(|r| r.section_sizes.get(&len));
+                (len, StatsEstimate::new(sizes))
+            })
+            .collect();
+        let all_sizes: Vec<_> = replicates.iter().map(|r| r.all_section_sizes()).collect();
+
+        Aggregate {
+            replicates: replicates.len(),
+            metrics,
+            age_distribution: aggregate_distributions(replicates.iter().map(|r| &r.age_distribution)),
+            drops_dist: aggregate_distributions(replicates.iter().map(|r| &r.output.drops_dist)),
+            network_structure,
+            section_sizes,
+            all_section_sizes: StatsEstimate::new(all_sizes.iter().map(Some)),
+        }
+    }
+
+    /// Writes the estimates to CSV files named `<prefix>_metrics.csv`, `<prefix>_age.csv`,
+    /// `<prefix>_drops.csv`, `<prefix>_structure.csv` and `<prefix>_sections.csv`
+    pub fn write_csv(&self, prefix: &str) -> io::Result<()> {
+        let create = |name: &str| -> io::Result<BufWriter<File>> {
+            Ok(BufWriter::new(File::create(format!("{}_{}.csv", prefix, name))?))
+        };
+
+        let mut file = create("metrics")?;
+        writeln!(file, "metric,mean,ci_low,ci_high")?;
+        for &(name, ref estimate) in &self.metrics {
+            writeln!(file, "{},{}", name, estimate.csv_fields())?;
+        }
+
+        for &(name, dist) in &[("age", &self.age_distribution), ("drops", &self.drops_dist)] {
+            let mut file = create(name)?;
+            writeln!(file, "age,mean,ci_low,ci_high")?;
+            for (age, estimate) in dist {
+                writeln!(file, "{},{}", age, estimate.csv_fields())?;
+            }
+        }
+
+        let mut file = create("structure")?;
+        writeln!(
+            file,
+            "iteration,size,size_ci_low,size_ci_high,sections,sections_ci_low,sections_ci_high,\
+             complete,complete_ci_low,complete_ci_high,third_malicious,third_malicious_ci_low,\
+             third_malicious_ci_high,half_malicious,half_malicious_ci_low,half_malicious_ci_high"
+        )?;
+        for (i, structure) in self.network_structure.iter().enumerate() {
+            writeln!(
+                file,
+                "{},{},{},{},{},{}",
+                i,
+                structure.size.csv_fields(),
+                structure.sections.csv_fields(),
+                structure.complete.csv_fields(),
+                structure.third_malicious.csv_fields(),
+                structure.half_malicious.csv_fields()
+            )?;
+        }
+
+        let mut file = create("sections")?;
+        writeln!(
+            file,
+            "prefix_len,count,count_ci_low,count_ci_high,average,average_ci_low,average_ci_high,\
+             min,min_ci_low,min_ci_high,max,max_ci_low,max_ci_high,\
+             standard_dev,standard_dev_ci_low,standard_dev_ci_high"
+        )?;
+        for (len, stats) in &self.section_sizes {
+            writeln!(file, "{},{}", len, stats.csv_fields())?;
+        }
+        writeln!(file, "all,{}", self.all_section_sizes.csv_fields())?;
+        Ok(())
+    }
+}
+
+// Display the estimates as markdown tables, like the summary of a single network
+impl fmt::Display for Aggregate {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        // Network summary
+        writeln!(fmt, "|    Metrics    |  Mean ± 95% CI ({} replicates) |", self.replicates)?;
+        writeln!(fmt, "|:--------------|---------:|")?;
+        for &(name, ref estimate) in &self.metrics {
+            writeln!(fmt, "| {:<13} | {} |", name, estimate)?;
+        }
+        writeln!(fmt)?;
+
+        // Distribution of sections per prefix length
+        writeln!(fmt, "| Prefix len {}", Stats::get_header_line())?;
+        writeln!(fmt, "|:-----------{}", Stats::get_separator_line())?;
+        let row = |fmt: &mut fmt::Formatter, label: &dyn fmt::Display, stats: &StatsEstimate| {
+            writeln!(
+                fmt,
+                "| {} | {} | {} | {} | {} | {} |",
+                label, stats.count, stats.average, stats.min, stats.max, stats.standard_deviation
+            )
+        };
+        for (len, stats) in &self.section_sizes {
+            row(fmt, len, stats)?;
+        }
+        row(fmt, &"All", &self.all_section_sizes)?;
+        writeln!(fmt)?;
+
+        // Age and drops distributions
+        writeln!(fmt, "| Age | Nodes | Drops |")?;
+        writeln!(fmt, "|----:|------:|------:|")?;
+        let ages: BTreeSet<u8> = self
+            .age_distribution
+            .keys()
+            .chain(self.drops_dist.keys())
+            .cloned()
+            .collect();
+        let (min_age, max_age) = match (ages.iter().next(), ages.iter().next_back()) {
+            (Some(&min), Some(&max)) => (min, max),
+            _ => return Ok(()),
+        };
+        for age in min_age..=max_age {
+            let get = |dist: &BTreeMap<u8, Estimate>| dist.get(&age).cloned().unwrap_or_default();
+            writeln!(fmt, "| {} | {} | {} |", age, get(&self.age_distribution), get(&self.drops_dist))?;
+        }
+        Ok(())
+    }
+}
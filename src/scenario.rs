@@ -0,0 +1,412 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use serde_json;
+use toml;
+use network::Network;
+use network::failure::Failure;
+use params::{deserialize_loaded, is_json, DropDist, ParamsError};
+
+/// A quantity of the network a stop condition looks at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// the number of nodes in the sections
+    Nodes,
+    /// the number of sections
+    Sections,
+    /// the number of complete sections
+    Complete,
+}
+
+/// A condition ending a phase early, written like `nodes>=10000` or `complete<=5`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StopCondition {
+    pub metric: Metric,
+    /// whether the metric has to reach the value from below, as opposed to from above
+    pub at_least: bool,
+    pub value: usize,
+}
+
+impl StopCondition {
+    /// Returns whether the network meets the condition
+    pub fn is_met(&self, network: &Network) -> bool {
+        let actual = match self.metric {
+            Metric::Nodes => network.sections().values().map(|s| s.len()).sum(),
+            Metric::Sections => network.num_sections(),
+            Metric::Complete => network.complete_sections(),
+        };
+        if self.at_least {
+            actual >= self.value
+        } else {
+            actual <= self.value
+        }
+    }
+}
+
+impl FromStr for StopCondition {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        let invalid = || ParamsError::InvalidValue("stop condition", s.to_owned());
+        let (metric, at_least, value) = if let Some(i) = s.find(">=") {
+            (&s[..i], true, &s[i + 2..])
+        } else if let Some(i) = s.find("<=") {
+            (&s[..i], false, &s[i + 2..])
+        } else {
+            return Err(invalid());
+        };
+        let metric = match metric.trim() {
+            "nodes" => Metric::Nodes,
+            "sections" => Metric::Sections,
+            "complete" => Metric::Complete,
+            _ => return Err(invalid()),
+        };
+        let value = value.trim().parse().map_err(|_| invalid())?;
+        Ok(StopCondition {
+            metric,
+            at_least,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for StopCondition {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let metric = match self.metric {
+            Metric::Nodes => "nodes",
+            Metric::Sections => "sections",
+            Metric::Complete => "complete",
+        };
+        let op = if self.at_least { ">=" } else { "<=" };
+        write!(fmt, "{}{}{}", metric, op, self.value)
+    }
+}
+
+/// Something that happens once in a phase
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    /// a mass failure
+    Failure(Failure),
+    /// the given number of new nodes joining at once, e.g. a flash crowd
+    Join(usize),
+}
+
+impl FromStr for Action {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        match s.strip_prefix("join:") {
+            Some(count) => count
+                .parse()
+                .map(Action::Join)
+                .map_err(|_| ParamsError::InvalidValue("event", s.to_owned())),
+            None => s.parse().map(Action::Failure),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Failure(ref failure) => write!(fmt, "{}", failure),
+            Action::Join(count) => write!(fmt, "join:{}", count),
+        }
+    }
+}
+
+/// A one-off event of a phase, written as `AT@ACTION`, e.g. `0@join:2000` or `500@30%`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhaseEvent {
+    /// the iterations (or the simulated time) from the start of the phase
+    pub at: f64,
+    pub action: Action,
+}
+
+impl FromStr for PhaseEvent {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        let invalid = || ParamsError::InvalidValue("event", s.to_owned());
+        let mut parts = s.splitn(2, '@');
+        let at = parts
+            .next()
+            .and_then(|at| at.parse().ok())
+            .filter(|&at: &f64| at >= 0.0)
+            .ok_or_else(invalid)?;
+        let action = parts.next().ok_or_else(invalid)?.parse()?;
+        Ok(PhaseEvent { at, action })
+    }
+}
+
+impl fmt::Display for PhaseEvent {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}@{}", self.at, self.action)
+    }
+}
+
+/// (De)serialisation of values through their `FromStr` and `Display` implementations
+mod string {
+    use std::fmt::Display;
+    use std::str::FromStr;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use params::ParamsError;
+
+    pub fn serialize<S: Serializer, T: Display>(values: &[T], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|value| value.to_string()))
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr<Err = ParamsError>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| s.parse().map_err(|err: ParamsError| D::Error::custom(err.to_string())))
+            .collect()
+    }
+}
+
+/// A part of a scenario with its own churn
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    /// the label of the phase in the results
+    pub name: String,
+    /// the number of iterations (or the simulated time) the phase lasts, unless a stop condition
+    /// ends it first
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// the add and drop probabilities of the phase; the ones of the previous phase if missing
+    #[serde(default)]
+    pub growth: Option<(u8, u8)>,
+    /// the drop distribution of the phase; the one of the previous phase if missing
+    #[serde(default)]
+    pub drop_dist: Option<DropDist>,
+    /// the one-off events of the phase, sorted by the point at which they happen
+    #[serde(default, with = "string")]
+    pub events: Vec<PhaseEvent>,
+    /// the conditions ending the phase early
+    #[serde(default, with = "string")]
+    pub stop: Vec<StopCondition>,
+}
+
+/// The contents of a scenario file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    phases: Vec<Phase>,
+}
+
+/// A run divided into phases with their own churn, loaded from a TOML or JSON file with a list
+/// of `phases`. The run ends with the last phase.
+/// Written as the path of the file it was loaded from, and serialised with the phases, so that
+/// the file isn't read again.
+#[derive(Clone, PartialEq)]
+pub struct Scenario {
+    file: String,
+    phases: Arc<Vec<Phase>>,
+}
+
+impl Scenario {
+    /// Loads the scenario from a TOML or JSON file (depending on the extension)
+    pub fn load(file: &str) -> Result<Scenario, ParamsError> {
+        let mut contents = String::new();
+        let _ = File::open(file)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|err| ParamsError::Io(file.to_owned(), err))?;
+        let scenario: ScenarioFile = if is_json(file) {
+            serde_json::from_str(&contents)
+                .map_err(|err| ParamsError::Format(file.to_owned(), err.to_string()))?
+        } else {
+            toml::from_str(&contents)
+                .map_err(|err| ParamsError::Format(file.to_owned(), err.to_string()))?
+        };
+        Scenario::new(file.to_owned(), scenario.phases)
+    }
+
+    /// Checks the phases loaded from the file and sorts their events
+    fn new(file: String, mut phases: Vec<Phase>) -> Result<Scenario, ParamsError> {
+        for phase in &phases {
+            let invalid = |what: &str| {
+                ParamsError::Format(file.clone(), format!("phase {:?} {}", phase.name, what))
+            };
+            if phase.duration.is_none() && phase.stop.is_empty() {
+                return Err(invalid("has neither a duration nor a stop condition"));
+            }
+            if !phase.duration.is_none_or(|duration| duration >= 0.0) {
+                return Err(invalid("has an invalid duration"));
+            }
+            if phase.growth.is_some_and(|(add, drop)| add as u16 + drop as u16 > 100) {
+                return Err(invalid("has add and drop probabilities over 100"));
+            }
+        }
+        for phase in &mut phases {
+            phase.events.sort_by(|a, b| a.at.partial_cmp(&b.at).unwrap());
+        }
+        if phases.is_empty() {
+            return Err(ParamsError::Format(file, "no phases".to_owned()));
+        }
+        Ok(Scenario {
+            file,
+            phases: Arc::new(phases),
+        })
+    }
+
+    /// Returns the phases of the scenario
+    pub fn phases(&self) -> &[Phase] {
+        &self.phases
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        Scenario::load(s)
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.file)
+    }
+}
+
+/// Shows the file the scenario was loaded from, without the phases
+impl fmt::Debug for Scenario {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{:?}", self.file)
+    }
+}
+
+/// A scenario as it is serialised: the file it was loaded from and its phases
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LoadedScenario {
+    file: String,
+    phases: Vec<Phase>,
+}
+
+impl Serialize for Scenario {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut scenario = serializer.serialize_struct("Scenario", 2)?;
+        scenario.serialize_field("file", &self.file)?;
+        scenario.serialize_field("phases", &*self.phases)?;
+        scenario.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Scenario {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_loaded(deserializer, |loaded: LoadedScenario| {
+            Scenario::new(loaded.file, loaded.phases)
+        })
+    }
+}
+
+/// The course of a phase in a run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhaseRecord {
+    pub name: String,
+    /// the iteration in which the phase started
+    pub start_iteration: usize,
+    /// the simulated time at which the phase started
+    pub start_time: f64,
+    /// the number of one-off events of the phase that happened
+    pub events_done: usize,
+    /// the iteration in which the phase ended, if it did
+    pub end_iteration: Option<usize>,
+    /// what ended the phase: its duration or one of its stop conditions
+    pub ended_by: Option<String>,
+}
+
+/// Displays the phase records as a markdown table
+pub struct PhaseTable<'a>(pub &'a [PhaseRecord]);
+
+impl<'a> fmt::Display for PhaseTable<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "| Phase | Start | End | Ended by |")?;
+        writeln!(fmt, "|:------|------:|----:|:---------|")?;
+        for record in self.0 {
+            writeln!(
+                fmt,
+                "| {} | {} | {} | {} |",
+                record.name,
+                record.start_iteration,
+                record
+                    .end_iteration
+                    .map_or("-".to_owned(), |i| i.to_string()),
+                record.ended_by.as_ref().map_or("-", |s| s)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+    use super::*;
+
+    #[test]
+    fn stop_conditions() {
+        for s in &["nodes>=10000", "sections<=3", "complete>=5"] {
+            let condition: StopCondition = s.parse().unwrap();
+            assert_eq!(condition.to_string(), *s);
+        }
+        let condition: StopCondition = " complete <= 5 ".parse().unwrap();
+        assert_eq!(
+            condition,
+            StopCondition {
+                metric: Metric::Complete,
+                at_least: false,
+                value: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_stop_conditions() {
+        for s in &[
+            "", "nodes", "nodes=5", "nodes>5", "elders>=5", ">=5", "age<=3", "nodes>=", "nodes<=-1",
+            "nodes>=x",
+        ] {
+            assert!(s.parse::<StopCondition>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn phase_events() {
+        for s in &["0@join:2000", "500@30%", "10@prefix:01"] {
+            let event: PhaseEvent = s.parse().unwrap();
+            assert_eq!(event.to_string(), *s);
+        }
+        for s in &["join:5", "@join:5", "-1@join:5", "0@join:x", "0@x"] {
+            assert!(s.parse::<PhaseEvent>().is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn scenario_file() {
+        let path = env::temp_dir().join(format!("ageing_sim_scenario_{}.toml", process::id()));
+        let file = path.to_str().unwrap().to_owned();
+        fs::write(
+            &path,
+            "[[phases]]\nname = \"growth\"\nstop = [\"nodes>=100\"]\ngrowth = [90, 7]\n\n\
+             [[phases]]\nname = \"failure\"\nduration = 50\nevents = [\"10@join:5\", \"0@30%\"]\n",
+        ).unwrap();
+        let scenario: Scenario = file.parse().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(scenario.phases().len(), 2);
+        let events: Vec<_> = scenario.phases()[1].events.iter().map(|e| e.to_string()).collect();
+        assert_eq!(events, vec!["0@30%", "10@join:5"]);
+
+        // the phases are serialised with the scenario, so the file isn't needed to read it back
+        let json = serde_json::to_string(&scenario).unwrap();
+        assert_eq!(serde_json::from_str::<Scenario>(&json).unwrap(), scenario);
+    }
+}
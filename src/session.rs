@@ -0,0 +1,171 @@
+use std::f64::consts::PI;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use params::{deserialize_loaded, ParamsError};
+use random::SeededRng;
+
+/// The distribution of the lengths of the nodes' sessions, in units of simulated time.
+/// Written as `name:parameters`, e.g. `exp:1000`, `weibull:0.5:800`, `pareto:1.5:100`,
+/// `lognormal:6:1.5` or `empirical:sessions.txt`; an empirical one is serialised with its
+/// samples, so that the file isn't read again.
+#[derive(Clone, PartialEq)]
+pub enum SessionDist {
+    /// exponential with the given mean
+    Exponential(f64),
+    /// Weibull with the given shape and scale
+    Weibull(f64, f64),
+    /// Pareto with the given shape and scale (the minimum session length)
+    Pareto(f64, f64),
+    /// log-normal with the given mean and standard deviation of the logarithm
+    LogNormal(f64, f64),
+    /// drawn uniformly from the session lengths listed in a file
+    Empirical(String, Arc<Vec<f64>>),
+}
+
+impl SessionDist {
+    /// Draws a session length
+    pub fn sample(&self, rng: &mut SeededRng) -> f64 {
+        match *self {
+            SessionDist::Exponential(mean) => -mean * unit_open(rng).ln(),
+            SessionDist::Weibull(shape, scale) => scale * (-unit_open(rng).ln()).powf(1.0 / shape),
+            SessionDist::Pareto(shape, scale) => scale * unit_open(rng).powf(-1.0 / shape),
+            SessionDist::LogNormal(mu, sigma) => (mu + sigma * standard_normal(rng)).exp(),
+            SessionDist::Empirical(_, ref samples) => samples[rng.random_range(0, samples.len())],
+        }
+    }
+
+    /// Checks whether the parameters of the distribution make sense
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        let valid = match *self {
+            SessionDist::Exponential(mean) => mean > 0.0,
+            SessionDist::Weibull(shape, scale) | SessionDist::Pareto(shape, scale) => {
+                shape > 0.0 && scale > 0.0
+            }
+            SessionDist::LogNormal(mu, sigma) => mu.is_finite() && sigma >= 0.0,
+            SessionDist::Empirical(_, ref samples) => {
+                !samples.is_empty() && samples.iter().all(|&length| length > 0.0)
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(ParamsError::InvalidValue("session", self.to_string()))
+        }
+    }
+}
+
+/// Returns a random number from (0, 1]
+pub fn unit_open(rng: &mut SeededRng) -> f64 {
+    1.0 - rng.random::<f64>()
+}
+
+/// Returns a random number from the standard normal distribution, using the Box-Muller transform
+fn standard_normal(rng: &mut SeededRng) -> f64 {
+    let radius = (-2.0 * unit_open(rng).ln()).sqrt();
+    radius * (2.0 * PI * rng.random::<f64>()).cos()
+}
+
+/// Reads the session lengths from a file: numbers separated by whitespace or commas, with lines
+/// starting with `#` skipped
+fn load_samples(file: &str) -> Result<Vec<f64>, ParamsError> {
+    let mut contents = String::new();
+    let _ = File::open(file)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|err| ParamsError::Io(file.to_owned(), err))?;
+    contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| ParamsError::Format(file.to_owned(), format!("invalid session length {:?}", value)))
+        })
+        .collect()
+}
+
+impl FromStr for SessionDist {
+    type Err = ParamsError;
+    fn from_str(s: &str) -> Result<Self, ParamsError> {
+        let invalid = || ParamsError::InvalidValue("session", s.to_owned());
+        let mut parts = s.splitn(2, ':');
+        let name = parts.next().unwrap_or("");
+        let rest = parts.next().ok_or_else(invalid)?;
+        if name == "empirical" {
+            let samples = load_samples(rest)?;
+            let dist = SessionDist::Empirical(rest.to_owned(), Arc::new(samples));
+            dist.validate()?;
+            return Ok(dist);
+        }
+        let args = rest
+            .split(':')
+            .map(|p| p.parse::<f64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let dist = match (name, &args[..]) {
+            ("exp", &[mean]) | ("exponential", &[mean]) => SessionDist::Exponential(mean),
+            ("weibull", &[shape, scale]) => SessionDist::Weibull(shape, scale),
+            ("pareto", &[shape, scale]) => SessionDist::Pareto(shape, scale),
+            ("lognormal", &[mu, sigma]) => SessionDist::LogNormal(mu, sigma),
+            _ => return Err(invalid()),
+        };
+        dist.validate()?;
+        Ok(dist)
+    }
+}
+
+impl fmt::Display for SessionDist {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SessionDist::Exponential(mean) => write!(fmt, "exp:{}", mean),
+            SessionDist::Weibull(shape, scale) => write!(fmt, "weibull:{}:{}", shape, scale),
+            SessionDist::Pareto(shape, scale) => write!(fmt, "pareto:{}:{}", shape, scale),
+            SessionDist::LogNormal(mu, sigma) => write!(fmt, "lognormal:{}:{}", mu, sigma),
+            SessionDist::Empirical(ref file, _) => write!(fmt, "empirical:{}", file),
+        }
+    }
+}
+
+/// Shows the distribution the way it's written, without the samples of an empirical one
+impl fmt::Debug for SessionDist {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self)
+    }
+}
+
+/// An empirical distribution as it is serialised: the file it was loaded from and its samples
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LoadedSamples {
+    empirical: String,
+    samples: Vec<f64>,
+}
+
+impl Serialize for SessionDist {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            SessionDist::Empirical(ref file, ref samples) => {
+                let mut dist = serializer.serialize_struct("SessionDist", 2)?;
+                dist.serialize_field("empirical", file)?;
+                dist.serialize_field("samples", &**samples)?;
+                dist.end()
+            }
+            _ => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionDist {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_loaded(deserializer, |loaded: LoadedSamples| {
+            let dist = SessionDist::Empirical(loaded.empirical, Arc::new(loaded.samples));
+            dist.validate()?;
+            Ok(dist)
+        })
+    }
+}
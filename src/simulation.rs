@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+use availability::Playback;
+use checkpoint::{self, CheckpointError};
+use clock::Clock;
+use network::{Network, Output};
+use network::attack::attacker;
+use network::failure::Failure;
+use network::prefix::Prefix;
+use network::section::Section;
+use network::trace::TraceRecord;
+use params::{Engine, Params};
+use scenario::{Action, PhaseRecord};
+
+/// A single run of the simulation.
+/// It owns the network and drives it one random churn event at a time, so that it can be
+/// stepped and inspected from the outside.
+#[derive(Clone)]
+pub struct Simulation {
+    network: Network,
+    /// the simulated clock, in the continuous-time engine
+    clock: Option<Clock>,
+    /// the playback of the availability trace, if it drives the simulation
+    playback: Option<Playback>,
+}
+
+impl Simulation {
+    /// Starts a new simulation with a fresh network
+    pub fn new(params: Params) -> Simulation {
+        let mut network = Network::new(params);
+        let playback = network
+            .params()
+            .availability
+            .clone()
+            .map(|trace| Playback::new(&mut network, &trace));
+        let clock = match network.params().engine {
+            Engine::Continuous if playback.is_none() => Some(Clock::new(&mut network)),
+            _ => None,
+        };
+        Simulation {
+            network,
+            clock,
+            playback,
+        }
+    }
+
+    /// Resumes a simulation from a checkpoint file
+    pub fn from_checkpoint(path: &str) -> Result<Simulation, CheckpointError> {
+        let restored = checkpoint::load(path)?;
+        Ok(Simulation {
+            network: restored.network,
+            clock: restored.clock,
+            playback: restored.playback,
+        })
+    }
+
+    /// Saves the full state of the simulation to a checkpoint file
+    pub fn save_checkpoint(&self, path: &str) -> Result<(), CheckpointError> {
+        checkpoint::save(
+            &self.network,
+            self.clock.as_ref(),
+            self.playback.as_ref(),
+            path,
+        )
+    }
+
+    /// Generates a single random event and processes the churn cascade that follows it.
+    /// In the continuous-time engine, the event is the next join or departure on the clock, and
+    /// with an availability trace, the next join or leave of the trace.
+    pub fn step(&mut self) {
+        self.advance_scenario();
+        // Let the attacker act...
+        attacker(self.params().attacker).act(&mut self.network);
+        // ... generate a random event, unless a mass failure or an event of the phase is due...
+        if let Some(failure) = self.due_failure() {
+            self.network.mass_failure(failure);
+        } else if let Some(action) = self.due_phase_event() {
+            match action {
+                Action::Failure(failure) => self.network.mass_failure(failure),
+                Action::Join(count) => {
+                    for _ in 0..count {
+                        let _ = self.network.add_random_node();
+                    }
+                }
+            }
+        } else if let Some(ref mut playback) = self.playback {
+            playback.advance(&mut self.network);
+            self.network.set_time(playback.time());
+        } else if let Some(ref mut clock) = self.clock {
+            let _ = clock.advance(&mut self.network);
+            self.network.set_time(clock.time());
+        } else {
+            random_event(&mut self.network);
+        }
+        // ... and process the churn cascade that may happen
+        // (every churn event may trigger other churn events, that
+        // may trigger others etc.)
+        self.network.process_events();
+        if let Some(ref mut playback) = self.playback {
+            playback.follow_renames(&mut self.network);
+        }
+        if let Some(ref mut clock) = self.clock {
+            clock.follow_renames(&mut self.network);
+        }
+        self.advance_scenario();
+    }
+
+    /// Returns the current iteration, or the simulated time if the simulation runs in time
+    fn now(&self) -> f64 {
+        if self.params().is_timed() {
+            self.time()
+        } else {
+            self.iteration() as f64
+        }
+    }
+
+    /// Ends the current phase of the scenario if its duration has passed or one of its stop
+    /// conditions is met, and starts the next one with its churn settings
+    fn advance_scenario(&mut self) {
+        let scenario = match self.params().scenario {
+            Some(ref scenario) => scenario.clone(),
+            None => return,
+        };
+        loop {
+            let (now, iteration) = (self.now(), self.iteration());
+            let started = self.output().phases.len();
+            if started > 0 {
+                let phase = &scenario.phases()[started - 1];
+                let record = self.network.output().phases.last().unwrap();
+                if record.end_iteration.is_some() {
+                    return;
+                }
+                let elapsed = now - self.phase_start(record);
+                let ended_by = if phase.duration.is_some_and(|duration| elapsed >= duration) {
+                    Some("duration".to_owned())
+                } else {
+                    phase
+                        .stop
+                        .iter()
+                        .find(|condition| condition.is_met(&self.network))
+                        .map(|condition| condition.to_string())
+                };
+                let record = self.network.output_mut().phases.last_mut().unwrap();
+                match ended_by {
+                    Some(ended_by) => {
+                        record.end_iteration = Some(iteration);
+                        record.ended_by = Some(ended_by);
+                    }
+                    None => return,
+                }
+            }
+            let phase = match scenario.phases().get(started) {
+                Some(phase) => phase,
+                None => return,
+            };
+            log!(self.params(), "Starting phase {:?}", phase.name);
+            if let Some(growth) = phase.growth {
+                self.params_mut().growth = growth;
+            }
+            if let Some(ref drop_dist) = phase.drop_dist {
+                self.params_mut().drop_dist = drop_dist.clone();
+            }
+            let start_time = self.time();
+            self.network.output_mut().phases.push(PhaseRecord {
+                name: phase.name.clone(),
+                start_iteration: iteration,
+                start_time,
+                events_done: 0,
+                end_iteration: None,
+                ended_by: None,
+            });
+        }
+    }
+
+    /// Returns the point at which the phase started, in the units of `now`
+    fn phase_start(&self, record: &PhaseRecord) -> f64 {
+        if self.params().is_timed() {
+            record.start_time
+        } else {
+            record.start_iteration as f64
+        }
+    }
+
+    /// Returns the next one-off event of the current phase, if it's time for it to happen, and
+    /// marks it as done
+    fn due_phase_event(&mut self) -> Option<Action> {
+        let scenario = self.params().scenario.clone()?;
+        let record = self.output().phases.last()?;
+        if record.end_iteration.is_some() {
+            return None;
+        }
+        let phase = &scenario.phases()[self.output().phases.len() - 1];
+        let elapsed = self.now() - self.phase_start(record);
+        let event = phase
+            .events
+            .get(record.events_done)
+            .filter(|event| event.at <= elapsed)?;
+        self.network.output_mut().phases.last_mut().unwrap().events_done += 1;
+        Some(event.action)
+    }
+
+    /// Returns the next scheduled failure, if it's time for it to happen, and marks it as done
+    fn due_failure(&mut self) -> Option<Failure> {
+        let now = self.now();
+        let failure = self
+            .params()
+            .failures
+            .get(self.output().scheduled_failures)
+            .filter(|scheduled| scheduled.at <= now)
+            .map(|scheduled| scheduled.failure)?;
+        self.network.output_mut().scheduled_failures += 1;
+        Some(failure)
+    }
+
+    /// Steps the simulation until the number of iterations set in the parameters is reached
+    pub fn run(&mut self) {
+        while !self.is_finished() {
+            self.step();
+        }
+    }
+
+    /// Returns whether all the iterations set in the parameters have been processed, the
+    /// simulated duration has passed in the continuous-time engine, the availability trace
+    /// has been played to the end, or the last phase of the scenario has ended
+    pub fn is_finished(&self) -> bool {
+        if let Some(ref scenario) = self.params().scenario {
+            let phases = &self.output().phases;
+            phases.len() == scenario.phases().len()
+                && phases.last().is_some_and(|record| record.end_iteration.is_some())
+        } else if let Some(ref playback) = self.playback {
+            playback.is_finished()
+        } else if let Some(ref clock) = self.clock {
+            clock.is_finished(&self.network)
+        } else {
+            self.iteration() >= self.params().iterations
+        }
+    }
+
+    /// Returns the simulated time, which is always 0 in the discrete engine
+    pub fn time(&self) -> f64 {
+        self.network.time()
+    }
+
+    /// Returns the number of iterations processed so far
+    pub fn iteration(&self) -> usize {
+        self.network.iteration()
+    }
+
+    /// Returns the simulation parameters
+    pub fn params(&self) -> &Params {
+        self.network.params()
+    }
+
+    /// Returns the simulation parameters for modification. The changes take effect from the next
+    /// step on.
+    pub fn params_mut(&mut self) -> &mut Params {
+        self.network.params_mut()
+    }
+
+    /// Enables or disables recording of the event trace
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.network.set_tracing(enabled)
+    }
+
+    /// Returns the trace records collected since the last call
+    pub fn take_trace(&mut self) -> Vec<TraceRecord> {
+        self.network.take_trace()
+    }
+
+    /// Returns the outputs collected so far
+    pub fn output(&self) -> &Output {
+        self.network.output()
+    }
+
+    /// Returns the number of nodes of every age in the network
+    pub fn age_distribution(&self) -> BTreeMap<u8, usize> {
+        self.network.age_distribution()
+    }
+
+    /// Returns all the sections in the network indexed by prefixes
+    pub fn sections(&self) -> &BTreeMap<Prefix, Section> {
+        self.network.sections()
+    }
+
+    /// Returns the simulated network
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+}
+
+/// Generates a random churn event in the network. There are three possible kinds:
+/// node joining, node leaving and node rejoining.
+fn random_event(network: &mut Network) {
+    let probs = network.params().growth;
+    let x = network.rng_mut().random_range(0, 100);
+    if x < probs.0 {
+        network.add_random_node();
+    } else if x >= probs.0 && x < probs.0 + probs.1 {
+        network.drop_random_node();
+    } else {
+        network.rejoin_random_node();
+    }
+}
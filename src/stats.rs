@@ -1,6 +1,8 @@
-use std::{fmt, usize};
+use std::collections::BTreeMap;
+use std::fmt;
 
 // Compute count, average, min, max and possibly standard deviation of a vec of usize values
+#[derive(Clone, Debug)]
 pub struct Stats {
     count: usize,
     average: f64,
@@ -32,23 +34,38 @@ impl Stats {
             for val in values {
                 variance += (*val as f64 - average).powi(2);
             }
-            variance = variance / (count - 1f64);
+            variance /= count - 1f64;
             let standard_deviation = variance.sqrt();
             Some(standard_deviation)
         };
         Stats {
             count: values.len(),
-            average: average,
-            min: min,
-            max: max,
-            standard_deviation: standard_deviation,
+            average,
+            min,
+            max,
+            standard_deviation,
         }
     }
+    pub fn count(&self) -> usize {
+        self.count
+    }
+    pub fn average(&self) -> f64 {
+        self.average
+    }
+    pub fn min(&self) -> usize {
+        self.min
+    }
+    pub fn max(&self) -> usize {
+        self.max
+    }
+    pub fn standard_deviation(&self) -> Option<f64> {
+        self.standard_deviation
+    }
     pub fn get_header_line() -> &'static str {
-        return &"| Count | Average | Min | Max | Standard dev |";
+        "| Count | Average | Min | Max | Standard dev |"
     }
     pub fn get_separator_line() -> &'static str {
-        return &"|------:|--------:|----:|----:|-------------:|";
+        "|------:|--------:|----:|----:|-------------:|"
     }
 }
 
@@ -56,10 +73,150 @@ impl Stats {
 impl fmt::Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let precision = f.precision().unwrap_or(2usize);
-        try!(write!(f, "{} | {:.*} | {} | {} | ", self.count, precision, self.average, self.min, self.max));
+        write!(f, "{} | {:.*} | {} | {} | ", self.count, precision, self.average, self.min, self.max)?;
         match self.standard_deviation {
             None => write!(f, "None |"),
             Some(standard_deviation) => write!(f, "{:.*} |", precision, standard_deviation),
         }
     }
 }
+
+// Compute mean, variance, skewness and excess kurtosis of a distribution given as a map from
+// values to the numbers of their occurrences
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Moments {
+    pub mean: f64,
+    pub variance: f64,
+    pub skewness: f64,
+    pub kurtosis: f64,
+}
+impl Moments {
+    pub fn from_distribution(dist: &BTreeMap<u8, usize>) -> Self {
+        let count = dist.values().sum::<usize>() as f64;
+        if count == 0f64 {
+            return Default::default();
+        }
+        let central_moment = |mean: f64, power: i32| {
+            dist.iter()
+                .map(|(&value, &num)| num as f64 * (value as f64 - mean).powi(power))
+                .sum::<f64>() / count
+        };
+        let mean = central_moment(0f64, 1);
+        let variance = central_moment(mean, 2);
+        let (skewness, kurtosis) = if variance > 0f64 {
+            (
+                central_moment(mean, 3) / variance.powf(1.5),
+                central_moment(mean, 4) / variance.powi(2) - 3f64,
+            )
+        } else {
+            (0f64, 0f64)
+        };
+        Moments {
+            mean,
+            variance,
+            skewness,
+            kurtosis,
+        }
+    }
+}
+
+// Two-sided 95% quantiles of Student's t distribution for 1 to 30 degrees of freedom
+const T_QUANTILES: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+// The same quantiles for larger degrees of freedom, with the normal quantile as the limit
+const T_QUANTILES_LARGE: [(f64, f64); 4] =
+    [(40.0, 2.021), (60.0, 2.000), (120.0, 1.980), (f64::INFINITY, 1.960)];
+
+// Two-sided 95% quantile of Student's t distribution for the given degrees of freedom (at least
+// 1); beyond 30, interpolated linearly in 1/df between the tabulated values
+fn t_quantile(df: usize) -> f64 {
+    if let Some(&t) = T_QUANTILES.get(df - 1) {
+        return t;
+    }
+    let df = df as f64;
+    let mut lower = (T_QUANTILES.len() as f64, T_QUANTILES[T_QUANTILES.len() - 1]);
+    for &upper in &T_QUANTILES_LARGE {
+        if df <= upper.0 {
+            let position = (1.0 / lower.0 - 1.0 / df) / (1.0 / lower.0 - 1.0 / upper.0);
+            return lower.1 + (upper.1 - lower.1) * position;
+        }
+        lower = upper;
+    }
+    lower.1
+}
+
+// Mean of a sample together with the half-width of its 95% confidence interval, which is
+// unknown if there are fewer than 2 values
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Estimate {
+    pub mean: f64,
+    pub half_width: Option<f64>,
+}
+impl Estimate {
+    pub fn new(values: &[f64]) -> Self {
+        let count = values.len();
+        if count == 0 {
+            return Default::default();
+        }
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let half_width = if count == 1 {
+            None
+        } else {
+            let variance =
+                values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1) as f64;
+            let t = t_quantile(count - 1);
+            Some(t * (variance / count as f64).sqrt())
+        };
+        Estimate { mean, half_width }
+    }
+    pub fn low(&self) -> Option<f64> {
+        self.half_width.map(|h| self.mean - h)
+    }
+    pub fn high(&self) -> Option<f64> {
+        self.half_width.map(|h| self.mean + h)
+    }
+    // The mean and the bounds of the confidence interval as CSV fields
+    pub fn csv_fields(&self) -> String {
+        let bound = |b: Option<f64>| b.map(|b| b.to_string()).unwrap_or_default();
+        format!("{},{},{}", self.mean, bound(self.low()), bound(self.high()))
+    }
+}
+
+// Display an estimate as "mean ± half-width"
+impl fmt::Display for Estimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision().unwrap_or(2usize);
+        match self.half_width {
+            None => write!(f, "{:.*}", precision, self.mean),
+            Some(half_width) => write!(f, "{:.*} ± {:.*}", precision, self.mean, precision, half_width),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn t_quantile_is_tabulated_up_to_30() {
+        assert_eq!(t_quantile(1), 12.706);
+        assert_eq!(t_quantile(30), 2.042);
+        assert_eq!(t_quantile(60), 2.000);
+        assert_eq!(t_quantile(120), 1.980);
+    }
+
+    #[test]
+    fn t_quantile_decreases_smoothly_to_the_normal_quantile() {
+        let mut previous = t_quantile(30);
+        for df in 31..1000 {
+            let t = t_quantile(df);
+            assert!(t < previous && previous - t < 0.005, "df {}: {} after {}", df, t, previous);
+            previous = t;
+        }
+        assert!(t_quantile(1_000_000) > 1.96 && t_quantile(1_000_000) < 1.961);
+    }
+}
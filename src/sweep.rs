@@ -0,0 +1,318 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use network::Network;
+use network::ageing::AgeingKind;
+use network::elders::ElderKind;
+use network::relocation::RelocationKind;
+use network::split::MergeKind;
+use params::{DropDist, Params, ParamsError, Strategy};
+use random::{self, Seed};
+use simulation::Simulation;
+use stats::{Moments, Stats};
+
+/// The values of the parameters to sweep over. Every combination of the values is run once with
+/// every seed; the other parameters are taken from the base parameters.
+#[derive(Clone, Debug)]
+pub struct SweepSpec {
+    pub init_age: Vec<u8>,
+    pub max_young: Vec<usize>,
+    pub split_strategy: Vec<Strategy>,
+    pub merge_strategy: Vec<Option<MergeKind>>,
+    pub drop_dist: Vec<DropDist>,
+    pub growth: Vec<(u8, u8)>,
+    pub inc_age: Vec<bool>,
+    pub relocation: Vec<RelocationKind>,
+    pub ageing: Vec<AgeingKind>,
+    pub elder_selection: Vec<ElderKind>,
+    pub seeds: Vec<Seed>,
+}
+
+impl SweepSpec {
+    /// Creates a sweep that only runs the base parameters with `num_seeds` seeds. The seeds are
+    /// derived from the base seed, if there is one.
+    pub fn new(base: &Params, num_seeds: usize) -> SweepSpec {
+        SweepSpec {
+            init_age: vec![base.init_age],
+            max_young: vec![base.max_young],
+            split_strategy: vec![base.split_strategy],
+            merge_strategy: vec![base.merge_strategy],
+            drop_dist: vec![base.drop_dist.clone()],
+            growth: vec![base.growth],
+            inc_age: vec![base.inc_age],
+            relocation: vec![base.relocation],
+            ageing: vec![base.ageing],
+            elder_selection: vec![base.elder_selection],
+            seeds: random::seeds(base.seed, num_seeds),
+        }
+    }
+
+    /// Returns the parameters of every run in the sweep
+    pub fn runs(&self, base: &Params) -> Vec<Params> {
+        let mut base = base.clone();
+        base.verbose = false;
+        base.structure_output_file = None;
+        base.lifecycle_output_file = None;
+        let runs = vec![base];
+        let runs = vary(runs, &self.init_age, |params, &init_age| params.init_age = init_age);
+        let runs = vary(runs, &self.max_young, |params, &max_young| params.max_young = max_young);
+        let runs = vary(runs, &self.split_strategy, |params, &strategy| {
+            params.split_strategy = strategy
+        });
+        let runs = vary(runs, &self.merge_strategy, |params, &strategy| {
+            params.merge_strategy = strategy
+        });
+        let runs = vary(runs, &self.drop_dist, |params, drop_dist| {
+            params.drop_dist = drop_dist.clone()
+        });
+        let runs = vary(runs, &self.growth, |params, &growth| params.growth = growth);
+        let runs = vary(runs, &self.inc_age, |params, &inc_age| params.inc_age = inc_age);
+        let runs = vary(runs, &self.relocation, |params, &policy| params.relocation = policy);
+        let runs = vary(runs, &self.ageing, |params, &rule| params.ageing = rule);
+        let runs = vary(runs, &self.elder_selection, |params, &selection| {
+            params.elder_selection = selection
+        });
+        vary(runs, &self.seeds, |params, &seed| params.seed = Some(seed))
+    }
+}
+
+/// Combines every run with every value, setting the value with `set`
+fn vary<T, F: Fn(&mut Params, &T)>(runs: Vec<Params>, values: &[T], set: F) -> Vec<Params> {
+    let mut result = vec![];
+    for params in runs {
+        for value in values {
+            let mut params = params.clone();
+            set(&mut params, value);
+            result.push(params);
+        }
+    }
+    result
+}
+
+/// Parses a comma-separated list of values
+pub fn parse_list<T: FromStr>(arg: &'static str, values: &str) -> Result<Vec<T>, ParamsError> {
+    values
+        .split(',')
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| ParamsError::InvalidValue(arg, value.to_owned()))
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of numbers and ranges of numbers, like `1,3..5,7..=9`
+pub fn parse_numbers<T: TryFrom<u64>>(arg: &'static str, values: &str) -> Result<Vec<T>, ParamsError> {
+    let mut result = vec![];
+    for value in values.split(',').map(str::trim) {
+        let invalid = || ParamsError::InvalidValue(arg, value.to_owned());
+        let parse = |s: &str| s.trim().parse::<u64>().map_err(|_| invalid());
+        let range = if let Some(pos) = value.find("..=") {
+            parse(&value[..pos])?..parse(&value[pos + 3..])? + 1
+        } else if let Some(pos) = value.find("..") {
+            parse(&value[..pos])?..parse(&value[pos + 2..])?
+        } else {
+            let number = parse(value)?;
+            number..number + 1
+        };
+        if range.start >= range.end {
+            return Err(invalid());
+        }
+        for number in range {
+            result.push(T::try_from(number).map_err(|_| invalid())?);
+        }
+    }
+    Ok(result)
+}
+
+/// Parses a comma-separated list of add and drop probabilities, like `90:7,80:15`
+pub fn parse_growth(arg: &'static str, values: &str) -> Result<Vec<(u8, u8)>, ParamsError> {
+    values
+        .split(',')
+        .map(|value| {
+            let invalid = || ParamsError::InvalidValue(arg, value.to_owned());
+            let mut parts = value.trim().split(':').map(|s| s.parse::<u8>());
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(Ok(add)), Some(Ok(drop)), None) => Ok((add, drop)),
+                _ => Err(invalid()),
+            }
+        })
+        .collect()
+}
+
+/// The final state of a single run, reduced to a handful of numbers
+#[derive(Clone, Debug)]
+pub struct RunSummary {
+    pub params: Params,
+    pub adds: u64,
+    pub drops: u64,
+    pub rejoins: u64,
+    pub relocations: u64,
+    pub rejections: u64,
+    pub churn: u64,
+    pub nodes: usize,
+    pub left_nodes: usize,
+    pub sections: usize,
+    pub complete: usize,
+    pub section_size: Stats,
+    pub age: Moments,
+    pub first_third_malicious: Option<usize>,
+    pub first_capture: Option<usize>,
+    pub relocation_distance: Option<f64>,
+    pub section_size_variance: Option<f64>,
+    pub elder_turnover: Option<f64>,
+    pub dkg_per_1000: Option<f64>,
+}
+
+impl RunSummary {
+    pub fn new(network: &Network) -> RunSummary {
+        let output = network.output();
+        let section_sizes: Vec<_> = network.sections().values().map(|s| s.len()).collect();
+        RunSummary {
+            params: network.params().clone(),
+            adds: output.adds,
+            drops: output.drops,
+            rejoins: output.rejoins,
+            relocations: output.relocations,
+            rejections: output.rejections,
+            churn: output.churn,
+            nodes: section_sizes.iter().sum(),
+            left_nodes: network.left_nodes().len(),
+            sections: network.num_sections(),
+            complete: network.complete_sections(),
+            section_size: Stats::new(&section_sizes),
+            age: Moments::from_distribution(&network.age_distribution()),
+            first_third_malicious: output.first_third_malicious,
+            first_capture: output.first_capture,
+            relocation_distance: output.relocation.mean_distance(),
+            section_size_variance: output.relocation.mean_size_variance(),
+            elder_turnover: output.elders.turnover(output.churn),
+            dkg_per_1000: output.elders.dkg_per_1000(output.churn),
+        }
+    }
+
+    pub fn csv_header() -> &'static str {
+        "run,init_age,max_young,split_strategy,drop_dist,p_add,p_drop,inc_age,seed,\
+         adds,drops,rejoins,relocations,rejections,churn,nodes,left_nodes,sections,complete,\
+         section_size_avg,section_size_sd,section_size_min,section_size_max,\
+         age_mean,age_variance,age_skewness,age_kurtosis,malicious_fraction,\
+         first_third_malicious,first_capture,relocation,relocation_distance,section_size_variance,ageing,elder_selection,elder_turnover,dkg_per_1000,merge_strategy"
+    }
+
+    pub fn csv_row(&self, run: usize) -> String {
+        let params = &self.params;
+        let iteration = |i: Option<usize>| i.map(|i| i.to_string()).unwrap_or_default();
+        let mean = |m: Option<f64>| m.map(|m| m.to_string()).unwrap_or_default();
+        format!(
+            "{},{},{},{},{},{},{},{},\"{:?}\",{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            run,
+            params.init_age,
+            params.max_young,
+            params.split_strategy,
+            params.drop_dist,
+            params.growth.0,
+            params.growth.1,
+            params.inc_age,
+            params.seed.unwrap_or_default(),
+            self.adds,
+            self.drops,
+            self.rejoins,
+            self.relocations,
+            self.rejections,
+            self.churn,
+            self.nodes,
+            self.left_nodes,
+            self.sections,
+            self.complete,
+            self.section_size.average(),
+            self.section_size.standard_deviation().unwrap_or(0.0),
+            self.section_size.min(),
+            self.section_size.max(),
+            self.age.mean,
+            self.age.variance,
+            self.age.skewness,
+            self.age.kurtosis,
+            params.malicious_fraction,
+            iteration(self.first_third_malicious),
+            iteration(self.first_capture),
+            params.relocation,
+            mean(self.relocation_distance),
+            mean(self.section_size_variance),
+            params.ageing,
+            params.elder_selection,
+            mean(self.elder_turnover),
+            mean(self.dkg_per_1000),
+            params.merge_kind(),
+        )
+    }
+}
+
+/// Runs a simulation for each of the parameters, on `threads` threads in parallel.
+/// Every finished simulation is passed to `summarise` on its thread, and the summary is passed
+/// to `report` on the calling thread as soon as it's ready, together with the index of the run.
+pub fn run_parallel<T, S, R>(runs: Vec<Params>, threads: usize, summarise: S, mut report: R)
+where
+    T: Send + 'static,
+    S: Fn(Simulation) -> T + Send + Sync + 'static,
+    R: FnMut(usize, T),
+{
+    let jobs = Arc::new(Mutex::new(runs.into_iter().enumerate().collect::<VecDeque<_>>()));
+    let summarise = Arc::new(summarise);
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<_> = (0..threads.max(1))
+        .map(|_| {
+            let jobs = Arc::clone(&jobs);
+            let summarise = Arc::clone(&summarise);
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let job = jobs.lock().unwrap().pop_front();
+                let (index, params) = match job {
+                    Some(job) => job,
+                    None => break,
+                };
+                let mut simulation = Simulation::new(params);
+                simulation.run();
+                if sender.send((index, summarise(simulation))).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+    for (index, result) in receiver {
+        report(index, result);
+    }
+    for worker in workers {
+        worker.join().expect("A simulation thread panicked!");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_and_ranges() {
+        let numbers: Vec<u8> = parse_numbers("group-size", "1, 3..5,7..=9").unwrap();
+        assert_eq!(numbers, vec![1, 3, 4, 7, 8, 9]);
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for values in &["", "x", "1,,2", "5..5", "5..=4", "-1", "1..x"] {
+            assert!(parse_numbers::<u8>("group-size", values).is_err(), "{:?}", values);
+        }
+        assert!(parse_numbers::<u8>("group-size", "256").is_err());
+    }
+
+    #[test]
+    fn growth_pairs() {
+        assert_eq!(parse_growth("growth", "90:7, 80:15").unwrap(), vec![(90, 7), (80, 15)]);
+        for values in &["90", "90:7:1", "90:x", "300:1"] {
+            assert!(parse_growth("growth", values).is_err(), "{:?}", values);
+        }
+    }
+}